@@ -1,46 +1,178 @@
-use crate::{entry::ActionType, menu};
+use crate::{
+    diagnostics::{self, Problem},
+    entry::ActionType,
+    layout::KeyboardLayout,
+    menu::{self, EntrySource},
+};
 use notify::{RecommendedWatcher, RecursiveMode, Watcher};
 use regex::Regex;
-use serde::Deserialize;
+use schemars::{gen::SchemaGenerator, schema::Schema, JsonSchema};
+use serde::{de::DeserializeOwned, Deserialize};
 use std::{
-    path::PathBuf,
+    collections::HashMap,
+    path::{Path, PathBuf},
     str::FromStr,
-    sync::{mpsc::channel, Mutex, MutexGuard},
+    sync::{mpsc::channel, Mutex},
     time::Duration,
 };
 use tauri::{AppHandle, Manager};
 use tauri_plugin_global_shortcut::{GlobalShortcutExt, Shortcut};
 use tauri_plugin_opener::OpenerExt;
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Deserialize, JsonSchema)]
 pub struct Config {
+    /// The JSON schema editors check the file against.
+    #[serde(rename = "$schema", default)]
+    #[allow(unused)]
+    pub schema: Option<String>,
+
+    /// Start the app when logging in.
+    #[serde(default)]
     #[cfg_attr(debug_assertions, allow(unused))]
     pub autostart: bool,
 
     #[serde(default)]
     pub global: Global,
 
+    #[schemars(schema_with = "menus_schema")]
+    pub menus: Vec<Menu>,
+
+    /// Other files to load menus from, as glob patterns relative to the
+    /// config directory, such as "menus/*.json". Every .json, .toml, .yaml
+    /// and .yml file in the menus.d directory is always loaded.
+    #[serde(default)]
+    pub include: Vec<String>,
+}
+
+/// A file of menus included by the config.
+#[derive(Debug, Deserialize, JsonSchema)]
+pub struct Include {
+    /// The JSON schema editors check the file against.
+    #[serde(rename = "$schema", default)]
+    #[allow(unused)]
+    pub schema: Option<String>,
+
+    #[schemars(schema_with = "menus_schema")]
     pub menus: Vec<Menu>,
 }
 
-#[derive(Debug, Deserialize)]
+/// The directory, inside the config directory, whose files are always
+/// included.
+const MENUS_DIR: &str = "menus.d";
+
+/// A format config files can be written in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    Json,
+    Toml,
+    Yaml,
+}
+
+impl Format {
+    /// The format of a file, by its extension.
+    fn of(path: &Path) -> Option<Self> {
+        match path.extension()?.to_str()? {
+            "json" => Some(Format::Json),
+            "toml" => Some(Format::Toml),
+            "yaml" | "yml" => Some(Format::Yaml),
+            _ => None,
+        }
+    }
+}
+
+/// The names the config file is looked for under, in order.
+const CONFIG_FILES: [&str; 4] = ["config.json", "config.toml", "config.yaml", "config.yml"];
+
+/// The config file in `config_dir` and its format, config.json unless only a
+/// TOML or YAML config exists.
+fn config_path(config_dir: &Path) -> (PathBuf, Format) {
+    let path = CONFIG_FILES
+        .iter()
+        .map(|name| config_dir.join(name))
+        .find(|path| path.exists())
+        .unwrap_or_else(|| config_dir.join(CONFIG_FILES[0]));
+    let format = Format::of(&path).unwrap_or(Format::Json);
+
+    (path, format)
+}
+
+#[derive(Debug, Deserialize, JsonSchema, Clone)]
 pub struct Global {
+    /// Characters that entries can be selected with. Any character can be
+    /// used when empty.
     #[serde(default)]
     pub allowed_chars: String,
+    /// Whether allowed_chars is case sensitive.
     #[serde(default)]
     pub match_allowed_chars_case: bool,
+    /// A regex that characters must match to select entries with.
     #[serde(default = "default_allowed_regex")]
     pub allowed_regex: String,
+    /// Whether selection letters are case sensitive, so Shift selects a
+    /// different entry.
     #[serde(default)]
     pub match_selection_case: bool,
+    /// Lets a letter select any accented form of it, for example e selects é.
+    #[serde(default)]
+    pub fold_diacritics: bool,
+    /// Tell entries apart in as few keys as possible. May make some key
+    /// sequences unintuitive. Changing one entry may change many sequences.
     #[serde(default)]
     pub minimize_keys: bool,
+    /// Hide file extensions. Only affects the displayed text.
     #[serde(default)]
     pub remove_extension: bool,
+    /// Custom CSS to apply to the window. Entries are made of `.pre`,
+    /// `.current` and `.post` elements.
     #[serde(default)]
+    #[schemars(example = "example_css")]
     pub custom_css: Option<String>,
+    /// Filenames to ignore in a directory.
     #[serde(default = "default_ignored_files")]
     pub ignored_files: Vec<String>,
+    /// Selecting a folder lists its contents instead of activating it. Press
+    /// Backspace with nothing typed, or Alt+Up, to return to the parent
+    /// folder.
+    #[serde(default)]
+    pub navigate_directories: bool,
+    /// Shell-quote the selection substituted into string commands, so names
+    /// containing quotes, $() or ; are passed literally. Don't surround {}
+    /// with quotes when enabled. On Windows, %VAR% in names is still
+    /// expanded.
+    #[serde(default)]
+    pub quote_placeholders: bool,
+    /// List frequently and recently used entries first, so they get the most
+    /// natural keys. Usage is stored in the data directory.
+    #[serde(default)]
+    pub frecency: bool,
+    /// Remember the keys entries were given and keep them while they are
+    /// still valid, so adding or removing an entry only changes the keys of
+    /// new or conflicting entries. Assignments are stored in the data
+    /// directory.
+    #[serde(default)]
+    pub stable_selections: bool,
+    /// "letter" selects entries by letters in their text. "hint" shows a
+    /// generated label next to each entry, made of allowed_chars, or home row
+    /// keys when fewer than two are set. Hints can select entries without any
+    /// usable letters.
+    #[serde(default)]
+    pub selection_mode: SelectionMode,
+    /// How minimize_keys assigns letters.
+    #[serde(default)]
+    pub selection_strategy: SelectionStrategy,
+    /// Prefer letters that start a word: after a space, _, -, . or /,
+    /// capitals in camelCase and the start of numbers.
+    #[serde(default)]
+    pub prefer_word_starts: bool,
+    /// The keyboard layout typed with. Letters on the home row and other easy
+    /// to reach keys are preferred, and keys are read by their physical
+    /// position so switching the system layout doesn't change selections.
+    #[serde(default)]
+    pub keyboard_layout: Option<KeyboardLayout>,
+    /// How strongly easy to reach keys are preferred over earlier letters
+    /// when a keyboard_layout is set.
+    #[serde(default = "default_key_effort_weight")]
+    pub key_effort_weight: usize,
 }
 
 impl Default for Global {
@@ -50,15 +182,82 @@ impl Default for Global {
             match_allowed_chars_case: false,
             allowed_regex: default_allowed_regex(),
             match_selection_case: false,
+            fold_diacritics: false,
             minimize_keys: false,
             remove_extension: false,
             custom_css: None,
             ignored_files: default_ignored_files(),
+            navigate_directories: false,
+            quote_placeholders: false,
+            frecency: false,
+            stable_selections: false,
+            selection_mode: SelectionMode::default(),
+            selection_strategy: SelectionStrategy::default(),
+            prefer_word_starts: false,
+            keyboard_layout: None,
+            key_effort_weight: default_key_effort_weight(),
+        }
+    }
+}
+
+impl Global {
+    /// These settings with the ones set in `overrides` replaced. Ignored files
+    /// are added to the global ones instead.
+    fn with_overrides(&self, overrides: &GlobalOverrides) -> Global {
+        Global {
+            allowed_chars: overrides
+                .allowed_chars
+                .clone()
+                .unwrap_or_else(|| self.allowed_chars.clone()),
+            match_allowed_chars_case: overrides
+                .match_allowed_chars_case
+                .unwrap_or(self.match_allowed_chars_case),
+            allowed_regex: overrides
+                .allowed_regex
+                .clone()
+                .unwrap_or_else(|| self.allowed_regex.clone()),
+            match_selection_case: overrides
+                .match_selection_case
+                .unwrap_or(self.match_selection_case),
+            fold_diacritics: overrides.fold_diacritics.unwrap_or(self.fold_diacritics),
+            minimize_keys: overrides.minimize_keys.unwrap_or(self.minimize_keys),
+            remove_extension: overrides.remove_extension.unwrap_or(self.remove_extension),
+            custom_css: overrides
+                .custom_css
+                .clone()
+                .or_else(|| self.custom_css.clone()),
+            ignored_files: [self.ignored_files.clone(), overrides.ignored_files.clone()].concat(),
+            navigate_directories: overrides
+                .navigate_directories
+                .unwrap_or(self.navigate_directories),
+            quote_placeholders: overrides
+                .quote_placeholders
+                .unwrap_or(self.quote_placeholders),
+            frecency: overrides.frecency.unwrap_or(self.frecency),
+            stable_selections: overrides
+                .stable_selections
+                .unwrap_or(self.stable_selections),
+            selection_mode: overrides.selection_mode.unwrap_or(self.selection_mode),
+            selection_strategy: overrides
+                .selection_strategy
+                .unwrap_or(self.selection_strategy),
+            prefer_word_starts: overrides
+                .prefer_word_starts
+                .unwrap_or(self.prefer_word_starts),
+            keyboard_layout: overrides
+                .keyboard_layout
+                .clone()
+                .or_else(|| self.keyboard_layout.clone()),
+            key_effort_weight: overrides
+                .key_effort_weight
+                .unwrap_or(self.key_effort_weight),
         }
     }
 }
 
-#[derive(Debug, Deserialize)]
+/// Global settings replaced for a single menu. Settings left out keep their
+/// global value.
+#[derive(Debug, Deserialize, JsonSchema)]
 pub struct GlobalOverrides {
     #[serde(default)]
     pub allowed_chars: Option<String>,
@@ -69,19 +268,68 @@ pub struct GlobalOverrides {
     #[serde(default)]
     pub match_selection_case: Option<bool>,
     #[serde(default)]
+    pub fold_diacritics: Option<bool>,
+    #[serde(default)]
     pub minimize_keys: Option<bool>,
     #[serde(default)]
     pub remove_extension: Option<bool>,
     #[serde(default)]
     pub custom_css: Option<String>,
+    /// Filenames to ignore in a directory, added to the global list rather
+    /// than replacing it.
     #[serde(default)]
     pub ignored_files: Vec<String>,
+    #[serde(default)]
+    pub navigate_directories: Option<bool>,
+    #[serde(default)]
+    pub quote_placeholders: Option<bool>,
+    #[serde(default)]
+    pub frecency: Option<bool>,
+    #[serde(default)]
+    pub stable_selections: Option<bool>,
+    #[serde(default)]
+    pub selection_mode: Option<SelectionMode>,
+    #[serde(default)]
+    pub selection_strategy: Option<SelectionStrategy>,
+    #[serde(default)]
+    pub prefer_word_starts: Option<bool>,
+    #[serde(default)]
+    pub keyboard_layout: Option<KeyboardLayout>,
+    #[serde(default)]
+    pub key_effort_weight: Option<usize>,
+}
+
+/// Whether entries are selected by typing letters from their text, or by
+/// typing a generated label shown next to them.
+#[derive(Debug, Deserialize, JsonSchema, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum SelectionMode {
+    #[default]
+    Letter,
+    Hint,
+}
+
+/// How `minimize_keys` assigns letters. `Greedy` gives each entry in order
+/// its first free letter. `Matching` gives as many entries as possible a
+/// letter of their own, and spreads the rest over the least used letters.
+/// This is a heuristic, which often needs fewer keys than `Greedy` but
+/// doesn't guarantee the fewest keystrokes overall.
+#[derive(Debug, Deserialize, JsonSchema, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum SelectionStrategy {
+    #[default]
+    Greedy,
+    Matching,
 }
 
 fn default_allowed_regex() -> String {
     "[A-z0-9]".to_string()
 }
 
+fn default_key_effort_weight() -> usize {
+    2
+}
+
 fn default_ignored_files() -> Vec<String> {
     vec![
         ".DS_Store".to_string(),
@@ -90,46 +338,195 @@ fn default_ignored_files() -> Vec<String> {
     ]
 }
 
-#[derive(Debug, Deserialize)]
+fn default_entries_command_timeout() -> u64 {
+    2000
+}
+
+fn example_hotkey() -> &'static str {
+    "Ctrl+Shift+KeyX"
+}
+
+fn example_command() -> &'static str {
+    "code {}"
+}
+
+fn example_css() -> &'static str {
+    ".current { color: blue; }"
+}
+
+/// The schema of a list of menus, with the rules between a menu's fields
+/// that can't be derived from its type.
+fn menus_schema(gen: &mut SchemaGenerator) -> Schema {
+    let menu = gen.subschema_for::<Menu>();
+    serde_json::from_value(serde_json::json!({
+        "type": "array",
+        "items": {
+            "allOf": [
+                menu,
+                { "anyOf": [{ "required": ["hotkey"] }, { "required": ["name"] }] },
+                {
+                    "anyOf": [
+                        { "required": ["directory"] },
+                        { "required": ["entries"] },
+                        { "required": ["entries_command"] }
+                    ]
+                },
+                {
+                    "if": {
+                        "properties": { "action": { "const": "command" } },
+                        "anyOf": [{ "required": ["directory"] }, { "required": ["entries_command"] }]
+                    },
+                    "then": { "required": ["command"] }
+                }
+            ]
+        }
+    }))
+    .expect("Could not build menus schema")
+}
+
+/// The schema of a command's arguments, which start with the program.
+fn argv_schema(gen: &mut SchemaGenerator) -> Schema {
+    let mut schema = gen.subschema_for::<Vec<String>>().into_object();
+    schema.array().min_items = Some(1);
+    schema.into()
+}
+
+/// The schema of modifier_actions, whose keys are modifiers.
+fn modifier_actions_schema(gen: &mut SchemaGenerator) -> Schema {
+    let mut schema = gen
+        .subschema_for::<HashMap<Modifier, ModifierAction>>()
+        .into_object();
+    schema.object().property_names = Some(Box::new(gen.subschema_for::<Modifier>()));
+    schema.into()
+}
+
+#[derive(Debug, Deserialize, JsonSchema)]
 #[serde(rename_all = "lowercase")]
 pub enum Action {
+    /// Opens the entry with its default app.
     Open,
+    /// Runs a command with the entry.
     Command,
 }
 
-#[derive(Debug, Deserialize)]
+/// A command either run through the shell, or run directly from a list of
+/// arguments, each templated separately.
+#[derive(Debug, Deserialize, JsonSchema, Clone)]
+#[serde(untagged)]
+pub enum CommandTemplate {
+    /// Run through the shell. Use {} or {path} as the placeholder for the
+    /// selection. {name}, {stem}, {ext} and {dir} are parts of its path,
+    /// {display} is the displayed text, {menu} is the menu's name and
+    /// {env:VAR} is an environment variable. Use {{ and }} for literal braces.
+    Shell(String),
+    /// The program and its arguments, run without a shell. Placeholders work
+    /// the same as in string commands, in any element.
+    #[schemars(schema_with = "argv_schema")]
+    Argv(Vec<String>),
+}
+
+#[derive(Debug, Deserialize, JsonSchema, Clone, Copy, PartialEq, Eq, Hash)]
+#[serde(rename_all = "lowercase")]
+pub enum Modifier {
+    Ctrl,
+    Alt,
+    Shift,
+}
+
+#[derive(Debug, Deserialize, JsonSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum BuiltinAction {
+    Open,
+    Reveal,
+    Copy,
+}
+
+#[derive(Debug, Deserialize, JsonSchema)]
+#[serde(untagged)]
+pub enum ModifierAction {
+    Builtin(BuiltinAction),
+    Command { command: CommandTemplate },
+}
+
+#[derive(Debug, Deserialize, JsonSchema)]
 #[serde(untagged)]
 pub enum Entry {
+    /// The entry's value, also shown as its text.
     Simple(String),
-    WithCommand { value: String, command: String },
+    /// An entry run with its own command.
+    WithCommand {
+        value: String,
+        command: CommandTemplate,
+    },
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Deserialize, JsonSchema)]
 pub struct Menu {
-    pub hotkey: String,
+    /// A case-insensitive hotkey of optional modifiers and a key, such as
+    /// Ctrl+Shift+KeyX, Alt+Space or Super+Digit5. Modifiers and keys are
+    /// listed at
+    /// https://github.com/tauri-apps/global-hotkey/blob/dev/src/hotkey.rs.
+    /// A menu needs a hotkey or a name.
+    #[schemars(example = "example_hotkey")]
+    pub hotkey: Option<String>,
+    /// Name used to open the menu from the command line with
+    /// `quick-find open <name>`. No two menus can share a name.
+    pub name: Option<String>,
+    /// What selecting an entry does.
     pub action: Action,
+    /// A directory whose files and folders are listed.
     pub directory: Option<String>,
+    /// List files in subdirectories as well. Entries are shown relative to
+    /// the directory.
+    #[serde(default)]
+    pub recursive: bool,
+    /// How many levels of subdirectories to descend into when recursive.
+    /// Folders past this depth are listed as entries. Unlimited if omitted.
+    #[serde(default)]
+    pub max_depth: Option<usize>,
+    /// Entries listed in the menu.
     pub entries: Option<Vec<Entry>>,
-    pub command: Option<String>,
+    /// A shell command run when the menu opens. Each line of its output
+    /// becomes an entry. A tab separates the displayed text from the value.
+    pub entries_command: Option<String>,
+    /// Milliseconds to wait for entries_command before giving up.
+    #[serde(default = "default_entries_command_timeout")]
+    pub entries_command_timeout: u64,
+    /// Milliseconds to reuse the output of entries_command for. Runs every
+    /// time the menu opens if omitted.
+    #[serde(default)]
+    pub entries_command_cache_ttl: Option<u64>,
+    /// The command to run entries with when the action is "command". Required
+    /// for directories and entries_command.
+    #[schemars(example = "example_command")]
+    pub command: Option<CommandTemplate>,
+    /// Alternative actions used when the last key of a selection is pressed
+    /// with a modifier. "reveal" shows the entry in the file manager and
+    /// "copy" copies its value to the clipboard. With match_selection_case,
+    /// Shift also changes the letter that is typed.
+    #[serde(default)]
+    #[schemars(schema_with = "modifier_actions_schema")]
+    pub modifier_actions: HashMap<Modifier, ModifierAction>,
+    /// Global settings replaced for this menu.
     #[serde(rename = "global_overrides")]
     pub global_overrides: Option<GlobalOverrides>,
 }
 
 #[tauri::command]
 pub fn open_config(app: AppHandle) {
-    let path = app
-        .path()
-        .config_dir()
-        .expect("Could not get config directory")
-        .join("quick-find")
-        .join("config.json");
+    let (path, _) = config_path(
+        &app.path()
+            .config_dir()
+            .expect("Could not get config directory")
+            .join("quick-find"),
+    );
 
     if app
         .opener()
         .open_path(path.to_string_lossy(), None::<&str>)
         .is_err()
     {
-        println!("Could not open config");
+        eprintln!("Could not open config");
     }
 }
 
@@ -140,15 +537,15 @@ pub fn ensure_exists(app: &AppHandle) {
         .expect("Could not get config directory")
         .join("quick-find");
 
-    let config_path = config_dir.join("config.json");
+    let (config_path, _) = config_path(&config_dir);
 
     if !config_dir.exists() {
-        println!("Creating config directory");
+        eprintln!("Creating config directory");
         std::fs::create_dir_all(&config_dir).expect("Could not create config directory");
     }
 
     if !config_path.exists() {
-        println!("Creating default config file");
+        eprintln!("Creating default config file");
         std::fs::write(
             &config_path,
             concat!(
@@ -159,7 +556,7 @@ pub fn ensure_exists(app: &AppHandle) {
                 "    {\n",
                 "      \"hotkey\": \"Ctrl+Space\",\n",
                 "      \"action\": \"open\",\n",
-                "      \"directory\": \"absolute/path/to/directory/\",\n",
+                "      \"directory\": \"absolute/path/to/directory/\"\n",
                 "    }\n",
                 "  ]\n",
                 "}"
@@ -172,107 +569,382 @@ pub fn ensure_exists(app: &AppHandle) {
     }
 }
 
-fn load(app: &AppHandle, config_dir: &PathBuf) -> Result<Config, serde_json::Error> {
-    let result = serde_json::from_str::<Config>(
-        &std::fs::read_to_string(config_dir.join("config.json")).expect("Could not read config"),
-    );
+/// Parses a config file, adding unknown keys as warnings and the error that
+/// stopped parsing, if any, to `problems`.
+fn parse<T: DeserializeOwned>(
+    text: &str,
+    format: Format,
+    problems: &mut Vec<Problem>,
+) -> Option<T> {
+    let mut ignored = Vec::new();
+    let mut on_ignored =
+        |path: serde_ignored::Path<'_>| ignored.push(diagnostics::ignored_path(&path));
 
-    match &result {
-        Ok(_res) => {
-            println!("Config loaded");
-            app.get_window("main")
-                .expect("Could not get window")
-                .hide()
-                .expect("Could not hide window");
+    let result = match format {
+        Format::Json => {
+            let mut deserializer = serde_json::Deserializer::from_str(text);
+            let result = serde_path_to_error::deserialize(serde_ignored::Deserializer::new(
+                &mut deserializer,
+                &mut on_ignored,
+            ));
 
-            #[cfg(not(debug_assertions))]
-            {
-                use tauri_plugin_autostart::ManagerExt;
+            match result {
+                Ok(value) => deserializer
+                    .end()
+                    .map(|()| value)
+                    .map_err(|e| json_problem(String::new(), e)),
+                Err(e) => Err(json_problem(
+                    diagnostics::error_path(e.path()),
+                    e.into_inner(),
+                )),
+            }
+        }
+        Format::Toml => serde_path_to_error::deserialize(serde_ignored::Deserializer::new(
+            toml::Deserializer::new(text),
+            &mut on_ignored,
+        ))
+        .map_err(|e| toml_problem(text, diagnostics::error_path(e.path()), e.into_inner())),
+        Format::Yaml => serde_path_to_error::deserialize(serde_ignored::Deserializer::new(
+            serde_yaml::Deserializer::from_str(text),
+            &mut on_ignored,
+        ))
+        .map_err(|e| yaml_problem(text, diagnostics::error_path(e.path()), e.into_inner())),
+    };
 
-                if _res.autostart {
-                    app.autolaunch().enable().expect("Could not enable autostart");
-                } else {
-                    app.autolaunch().disable().expect("Could not disable autostart");
-                }
+    for path in ignored {
+        problems.push(Problem::warning(
+            path,
+            "unknown setting, it is ignored".to_string(),
+        ));
+    }
+
+    match result {
+        Ok(value) => Some(value),
+        Err(problem) => {
+            problems.push(problem);
+            None
+        }
+    }
+}
+
+/// Parses the config, then hides the window and applies the autostart
+/// setting.
+fn load(
+    app: &AppHandle,
+    text: &str,
+    format: Format,
+    problems: &mut Vec<Problem>,
+) -> Option<Config> {
+    let config: Config = parse(text, format, problems)?;
+
+    eprintln!("Config loaded");
+    app.get_window("main")
+        .expect("Could not get window")
+        .hide()
+        .expect("Could not hide window");
+
+    #[cfg(not(debug_assertions))]
+    {
+        use tauri_plugin_autostart::ManagerExt;
+
+        if config.autostart {
+            app.autolaunch()
+                .enable()
+                .expect("Could not enable autostart");
+        } else {
+            app.autolaunch()
+                .disable()
+                .expect("Could not disable autostart");
+        }
+    }
+
+    Some(config)
+}
+
+/// The files included by `patterns`, followed by those in menus.d, in order
+/// and without duplicates or config files themselves. Problems with the patterns
+/// are added to `problems`.
+fn included_files(
+    config_dir: &Path,
+    patterns: &[String],
+    problems: &mut Vec<Problem>,
+) -> Vec<PathBuf> {
+    let mut files = Vec::new();
+
+    for (i, pattern) in patterns.iter().enumerate() {
+        let paths = match glob::glob(&config_dir.join(pattern).to_string_lossy()) {
+            Ok(paths) => paths,
+            Err(e) => {
+                problems.push(Problem::error(
+                    format!("include[{}]", i),
+                    format!("pattern {} could not be parsed: {}", pattern, e.msg),
+                ));
+                continue;
             }
+        };
+
+        let matched: Vec<PathBuf> = paths
+            .filter_map(Result::ok)
+            .filter(|path| path.is_file())
+            .collect();
+        if matched.is_empty() {
+            problems.push(Problem::warning(
+                format!("include[{}]", i),
+                format!("pattern {} matches no files", pattern),
+            ));
+        }
+        files.extend(matched);
+    }
+
+    if let Ok(dir) = std::fs::read_dir(config_dir.join(MENUS_DIR)) {
+        let mut menus_files: Vec<PathBuf> = dir
+            .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+            .filter(|path| path.is_file() && Format::of(path).is_some())
+            .collect();
+        menus_files.sort();
+        files.extend(menus_files);
+    }
+
+    let config_paths: Vec<PathBuf> = CONFIG_FILES
+        .iter()
+        .map(|name| config_dir.join(name))
+        .collect();
+    let mut unique: Vec<PathBuf> = Vec::new();
+    for file in files {
+        if !config_paths.contains(&file) && !unique.contains(&file) {
+            unique.push(file);
         }
-        Err(e) => crate::error(app, format!("Config invalid: {}", e)),
     }
 
-    result
+    unique
 }
 
-fn generate_menus(app: &AppHandle, mut menus: MutexGuard<Vec<crate::Menu>>, config: Config) {
-    let global_shortcut = app.global_shortcut();
-    global_shortcut
-        .unregister_all()
-        .expect("Could not unregister existing hotkeys");
-
-    menus.clear();
-
-    for menu in config.menus {
-        let shortcut_opt = Shortcut::from_str(menu.hotkey.as_str());
-        if shortcut_opt.is_err() {
-            crate::error(
-                app,
-                format!(
-                    "Shortcut {} could not be parsed, the menu will be skipped",
-                    menu.hotkey
-                ),
+/// The config file and every file it includes, reading only its include patterns
+/// so that files are found even when the rest of the config is invalid.
+fn config_files(config_dir: &Path) -> Vec<PathBuf> {
+    #[derive(Deserialize, Default)]
+    struct Includes {
+        #[serde(default)]
+        include: Vec<String>,
+    }
+
+    let (config_path, format) = config_path(config_dir);
+    let includes: Includes = std::fs::read_to_string(&config_path)
+        .ok()
+        .and_then(|text| parse(&text, format, &mut Vec::new()))
+        .unwrap_or_default();
+
+    let mut files = vec![config_path];
+    files.extend(included_files(
+        config_dir,
+        &includes.include,
+        &mut Vec::new(),
+    ));
+    files
+}
+
+fn json_problem(path: String, e: serde_json::Error) -> Problem {
+    // The position is kept separately, as it is where parsing stopped
+    let message = e.to_string();
+    let suffix = format!(" at line {} column {}", e.line(), e.column());
+
+    Problem {
+        position: Some((e.line(), e.column())),
+        ..Problem::error(
+            path,
+            message
+                .strip_suffix(&suffix)
+                .unwrap_or(&message)
+                .to_string(),
+        )
+    }
+}
+
+fn toml_problem(text: &str, path: String, e: toml::de::Error) -> Problem {
+    Problem {
+        position: e.span().map(|span| diagnostics::position(text, span.start)),
+        ..Problem::error(path, e.message().to_string())
+    }
+}
+
+fn yaml_problem(text: &str, path: String, e: serde_yaml::Error) -> Problem {
+    // Like JSON errors, the message ends with the position
+    let message = e.to_string();
+
+    Problem {
+        position: e
+            .location()
+            .map(|location| diagnostics::position(text, location.index())),
+        ..Problem::error(
+            path,
+            message
+                .split(" at line ")
+                .next()
+                .unwrap_or(&message)
+                .to_string(),
+        )
+    }
+}
+
+/// Builds the menus of every config file, given by its index, without
+/// registering hotkeys, adding every problem found to `problems`. Menus with
+/// errors are left out. Also returns the file and index in it of each menu.
+/// `files` holds every file, by index, to name them in problems.
+fn generate_menus(
+    global: &Global,
+    sources: Vec<(usize, Vec<Menu>)>,
+    files: &[diagnostics::Source],
+    problems: &mut Vec<Problem>,
+) -> (Vec<crate::Menu>, Vec<(usize, usize)>) {
+    let mut menus = Vec::new();
+    let mut origins = Vec::new();
+    let mut hotkeys: Vec<((usize, usize), Shortcut)> = Vec::new();
+    let mut names: Vec<((usize, usize), String)> = Vec::new();
+
+    let sources = sources.into_iter().flat_map(|(file, file_menus)| {
+        file_menus
+            .into_iter()
+            .enumerate()
+            .map(move |(i, menu)| (file, i, menu))
+    });
+
+    for (file, i, menu) in sources {
+        if menu.hotkey.is_none() && menu.name.is_none() {
+            problems.push(
+                Problem::error(
+                    format!("menus[{}]", i),
+                    "the menu has neither a hotkey nor a name".to_string(),
+                )
+                .in_file(file),
             );
             continue;
-        };
-        let shortcut = shortcut_opt.unwrap();
-
-        let settings = match menu.global_overrides {
-            Some(ref g) => &Global {
-                allowed_chars: g
-                    .allowed_chars
-                    .clone()
-                    .unwrap_or_else(|| config.global.allowed_chars.clone()),
-                match_allowed_chars_case: g
-                    .match_allowed_chars_case
-                    .unwrap_or(config.global.match_allowed_chars_case),
-                allowed_regex: g
-                    .allowed_regex
-                    .clone()
-                    .unwrap_or_else(|| config.global.allowed_regex.clone()),
-                match_selection_case: g
-                    .match_selection_case
-                    .unwrap_or(config.global.match_selection_case),
-                minimize_keys: g.minimize_keys.unwrap_or(config.global.minimize_keys),
-                remove_extension: g.remove_extension.unwrap_or(config.global.remove_extension),
-                custom_css: g
-                    .custom_css
-                    .clone()
-                    .or_else(|| config.global.custom_css.clone()),
-                ignored_files: [config.global.ignored_files.clone(), g.ignored_files.clone()]
-                    .concat(),
+        }
+
+        let shortcut = match &menu.hotkey {
+            Some(hotkey) => match Shortcut::from_str(hotkey.as_str()) {
+                Ok(shortcut) => Some(shortcut),
+                Err(_) => {
+                    problems.push(
+                        Problem::error(
+                            format!("menus[{}].hotkey", i),
+                            format!("hotkey {} could not be parsed", hotkey),
+                        )
+                        .in_file(file),
+                    );
+                    continue;
+                }
             },
-            None => &config.global,
+            None => None,
+        };
+
+        if let Some(shortcut) = shortcut {
+            if let Some(((other_file, other_index), _)) =
+                hotkeys.iter().find(|(_, other)| *other == shortcut)
+            {
+                problems.push(
+                    Problem::error(
+                        format!("menus[{}].hotkey", i),
+                        format!(
+                            "hotkey is also used by {}: menus[{}]",
+                            files[*other_file].name, other_index
+                        ),
+                    )
+                    .in_file(file),
+                );
+                continue;
+            }
+            hotkeys.push(((file, i), shortcut));
+        }
+
+        if let Some(name) = &menu.name {
+            if let Some(((other_file, other_index), _)) =
+                names.iter().find(|(_, other)| other == name)
+            {
+                problems.push(
+                    Problem::error(
+                        format!("menus[{}].name", i),
+                        format!(
+                            "name is also used by {}: menus[{}]",
+                            files[*other_file].name, other_index
+                        ),
+                    )
+                    .in_file(file),
+                );
+                continue;
+            }
+            names.push(((file, i), name.clone()));
+        }
+
+        if menu.max_depth.is_some() && !menu.recursive {
+            problems.push(
+                Problem::warning(
+                    format!("menus[{}].max_depth", i),
+                    "has no effect without recursive".to_string(),
+                )
+                .in_file(file),
+            );
+        }
+        if menu.recursive && menu.directory.is_none() {
+            problems.push(
+                Problem::warning(
+                    format!("menus[{}].recursive", i),
+                    "has no effect without directory".to_string(),
+                )
+                .in_file(file),
+            );
+        }
+        if menu.entries_command_cache_ttl.is_some() && menu.entries_command.is_none() {
+            problems.push(
+                Problem::warning(
+                    format!("menus[{}].entries_command_cache_ttl", i),
+                    "has no effect without entries_command".to_string(),
+                )
+                .in_file(file),
+            );
+        }
+
+        if matches!(menu.action, Action::Command)
+            && menu.command.is_none()
+            && (menu.directory.is_some() || menu.entries_command.is_some())
+        {
+            problems.push(
+                Problem::error(
+                    format!("menus[{}]", i),
+                    "the menu lists generated entries with action \"command\", but has no command"
+                        .to_string(),
+                )
+                .in_file(file),
+            );
+            continue;
+        }
+
+        let settings = match &menu.global_overrides {
+            Some(overrides) => global.with_overrides(overrides),
+            None => global.clone(),
         };
 
         let entries = menu
             .entries
             .unwrap_or_default()
             .iter()
-            .filter_map(|x| match x {
+            .enumerate()
+            .filter_map(|(j, x)| match x {
                 Entry::Simple(string) => {
                     let action_type = match menu.action {
                         Action::Open => ActionType::Open,
                         Action::Command => {
                             if let Some(cmd) = &menu.command {
-                                ActionType::Command(cmd.clone())
+                                ActionType::Command {
+                                    template: cmd.clone(),
+                                    quote: settings.quote_placeholders,
+                                }
                             } else {
-                                crate::error(
-                                    app,
-                                    format!(
-                                        concat!(
-                                            "Entry and menu don't have commands, ",
-                                            "skipping: {}"
-                                        ),
-                                        string
-                                    ),
+                                problems.push(
+                                    Problem::error(
+                                        format!("menus[{}].entries[{}]", i, j),
+                                        "neither the entry nor the menu has a command".to_string(),
+                                    )
+                                    .in_file(file),
                                 );
                                 return None;
                             }
@@ -287,19 +959,20 @@ fn generate_menus(app: &AppHandle, mut menus: MutexGuard<Vec<crate::Menu>>, conf
                 Entry::WithCommand { value, command } => {
                     let action_type = match menu.action {
                         Action::Open => {
-                            crate::error(
-                                app,
-                                format!(
-                                    concat!(
-                                        "Action is \"open\", ",
-                                        "yet the entry has a command, skipping: {}"
-                                    ),
-                                    value
-                                ),
+                            problems.push(
+                                Problem::error(
+                                    format!("menus[{}].entries[{}].command", i, j),
+                                    "the menu's action is \"open\", yet the entry has a command"
+                                        .to_string(),
+                                )
+                                .in_file(file),
                             );
                             return None;
                         }
-                        Action::Command => ActionType::Command(command.clone()),
+                        Action::Command => ActionType::Command {
+                            template: command.clone(),
+                            quote: settings.quote_placeholders,
+                        },
                     };
                     Some(crate::entry::Entry::new(
                         value.clone(),
@@ -310,17 +983,48 @@ fn generate_menus(app: &AppHandle, mut menus: MutexGuard<Vec<crate::Menu>>, conf
             })
             .collect();
 
+        let modifier_actions = menu
+            .modifier_actions
+            .iter()
+            .map(|(modifier, action)| {
+                let action_type = match action {
+                    ModifierAction::Builtin(BuiltinAction::Open) => ActionType::Open,
+                    ModifierAction::Builtin(BuiltinAction::Reveal) => ActionType::Reveal,
+                    ModifierAction::Builtin(BuiltinAction::Copy) => ActionType::Copy,
+                    ModifierAction::Command { command } => ActionType::Command {
+                        template: command.clone(),
+                        quote: settings.quote_placeholders,
+                    },
+                };
+                (*modifier, action_type)
+            })
+            .collect();
+
         let regex: Option<Regex>;
         if !settings.allowed_regex.is_empty() {
             let regex_res = Regex::new(settings.allowed_regex.as_str());
             if regex_res.is_err() {
-                crate::error(
-                    app,
-                    format!(
-                        "Regex {} could not be parsed, the menu will be skipped",
-                        settings.allowed_regex
-                    ),
-                );
+                let overridden = menu
+                    .global_overrides
+                    .as_ref()
+                    .is_some_and(|g| g.allowed_regex.is_some());
+                let message = format!("regex {} could not be parsed", settings.allowed_regex);
+
+                if overridden {
+                    problems.push(
+                        Problem::error(
+                            format!("menus[{}].global_overrides.allowed_regex", i),
+                            message,
+                        )
+                        .in_file(file),
+                    );
+                } else if !problems
+                    .iter()
+                    .any(|problem| problem.path == "global.allowed_regex")
+                {
+                    // The global regex is in the config file, and only reported once
+                    problems.push(Problem::error("global.allowed_regex".to_string(), message));
+                }
                 continue;
             }
             regex = Some(regex_res.unwrap());
@@ -330,26 +1034,326 @@ fn generate_menus(app: &AppHandle, mut menus: MutexGuard<Vec<crate::Menu>>, conf
 
         menus.push(crate::menu::Menu::new(
             shortcut,
-            entries,
+            menu.name,
             menu.action,
-            menu.directory,
-            settings.allowed_chars.clone(),
-            settings.match_allowed_chars_case,
-            regex,
-            settings.match_selection_case,
-            settings.minimize_keys,
-            settings.remove_extension,
             menu.command,
-            settings.custom_css.clone(),
-            settings.ignored_files.clone(),
+            modifier_actions,
+            regex,
+            &settings,
+            EntrySource {
+                entries,
+                directory: menu.directory,
+                recursive: menu.recursive,
+                max_depth: menu.max_depth,
+                entries_command: menu.entries_command,
+                entries_command_timeout: Duration::from_millis(menu.entries_command_timeout),
+                entries_command_cache_ttl: menu
+                    .entries_command_cache_ttl
+                    .map(Duration::from_millis),
+            },
         ));
+        origins.push((file, i));
+    }
 
-        app.global_shortcut()
-            .register(shortcut)
-            .expect("Could not register shortcut");
+    (menus, origins)
+}
+
+/// Registers the hotkeys of `new` in place of those of `old`. Hotkeys are
+/// only unregistered once all new ones are registered, so if any fails, the
+/// old ones are still in place and the failures are returned, one per menu.
+/// `origins` holds the file and index in it of each menu in `new`.
+fn swap_hotkeys(
+    app: &AppHandle,
+    old: &[crate::Menu],
+    new: &[crate::Menu],
+    origins: &[(usize, usize)],
+) -> Vec<Problem> {
+    let global_shortcut = app.global_shortcut();
+    let old_shortcuts: Vec<Shortcut> = old.iter().filter_map(|menu| menu.shortcut).collect();
+    let mut added = Vec::new();
+    let mut problems = Vec::new();
+
+    for (i, menu) in new.iter().enumerate() {
+        let Some(shortcut) = menu.shortcut else {
+            continue;
+        };
+        if old_shortcuts.contains(&shortcut) {
+            continue;
+        }
+
+        match global_shortcut.register(shortcut) {
+            Ok(()) => added.push(shortcut),
+            Err(e) => problems.push(register_problem(origins[i], e)),
+        }
     }
+
+    let removed = if problems.is_empty() {
+        old_shortcuts
+            .into_iter()
+            .filter(|shortcut| !new.iter().any(|menu| menu.shortcut == Some(*shortcut)))
+            .collect()
+    } else {
+        added
+    };
+
+    for shortcut in removed {
+        if let Err(e) = global_shortcut.unregister(shortcut) {
+            eprintln!(
+                "Could not unregister hotkey {}: {}",
+                shortcut.into_string(),
+                e
+            );
+        }
+    }
+
+    problems
 }
 
+/// Registers the hotkeys of `menus` when no menus are loaded yet, leaving out
+/// the menus whose hotkey can't be registered, with a problem for each.
+/// `origins` holds the file and index in it of each menu.
+fn register_hotkeys(
+    app: &AppHandle,
+    menus: Vec<crate::Menu>,
+    origins: &[(usize, usize)],
+) -> (Vec<crate::Menu>, Vec<Problem>) {
+    let global_shortcut = app.global_shortcut();
+    let mut registered = Vec::new();
+    let mut problems = Vec::new();
+
+    for (menu, origin) in menus.into_iter().zip(origins) {
+        if let Some(shortcut) = menu.shortcut {
+            if let Err(e) = global_shortcut.register(shortcut) {
+                problems.push(register_problem(*origin, e));
+                continue;
+            }
+        }
+        registered.push(menu);
+    }
+
+    (registered, problems)
+}
+
+fn register_problem((file, index): (usize, usize), e: impl std::fmt::Display) -> Problem {
+    Problem::error(
+        format!("menus[{}].hotkey", index),
+        format!("hotkey could not be registered: {}", e),
+    )
+    .in_file(file)
+}
+
+/// Builds the menu used by `--dmenu`, taking global settings from the config
+/// when it can be read and the defaults otherwise.
+pub fn dmenu_menu(app: &AppHandle, entries: Vec<crate::entry::Entry>) -> crate::Menu {
+    let global = app
+        .path()
+        .config_dir()
+        .ok()
+        .and_then(|dir| {
+            let (path, format) = config_path(&dir.join("quick-find"));
+            let text = std::fs::read_to_string(path).ok()?;
+            parse::<Config>(&text, format, &mut Vec::new())
+        })
+        .map(|config| config.global)
+        .unwrap_or_default();
+
+    let regex = if global.allowed_regex.is_empty() {
+        None
+    } else {
+        match Regex::new(global.allowed_regex.as_str()) {
+            Ok(regex) => Some(regex),
+            Err(_) => {
+                eprintln!(
+                    "Regex {} could not be parsed, it will be ignored",
+                    global.allowed_regex
+                );
+                None
+            }
+        }
+    };
+
+    crate::menu::Menu::new(
+        None,
+        None,
+        Action::Open,
+        None,
+        HashMap::new(),
+        regex,
+        &Global {
+            navigate_directories: false,
+            ..global
+        },
+        EntrySource {
+            entries,
+            ..EntrySource::default()
+        },
+    )
+}
+
+/// The JSON schema of the config file, generated from its types.
+pub fn schema() -> String {
+    serde_json::to_string_pretty(&schemars::schema_for!(Config))
+        .expect("Could not serialize config schema")
+}
+
+/// Loads the config and the files it includes and regenerates the menus,
+/// closing the open menu once they are replaced. If any file has errors or a
+/// hotkey can't be registered, the previous menus and hotkeys are kept. When
+/// no menus are loaded yet, as on startup, there are none to keep, so the
+/// valid menus are loaded and the rest reported. Every problem found,
+/// including warnings, is shown together.
+pub fn reload(app: &AppHandle) -> Result<(), String> {
+    let config_dir = app
+        .path()
+        .config_dir()
+        .expect("Could not get config directory")
+        .join("quick-find");
+
+    let (config_path, format) = config_path(&config_dir);
+    // Editors may remove the file for a moment while saving
+    let (text, read_problem) = match std::fs::read_to_string(&config_path) {
+        Ok(text) => (text, None),
+        Err(e) => (
+            String::new(),
+            Some(Problem::error(
+                String::new(),
+                format!("could not be read: {}", e),
+            )),
+        ),
+    };
+
+    let mut sources = vec![diagnostics::Source {
+        name: config_path
+            .file_name()
+            .unwrap_or_default()
+            .to_string_lossy()
+            .to_string(),
+        text,
+        format,
+    }];
+    let mut problems: Vec<Problem> = read_problem.into_iter().collect();
+    let menus_state = app.state::<Mutex<Vec<crate::Menu>>>();
+    let first_load;
+
+    let config = if problems.is_empty() {
+        load(app, &sources[0].text, format, &mut problems)
+    } else {
+        None
+    };
+    if let Some(config) = config {
+        let mut menu_sources = vec![(0, config.menus)];
+
+        for path in included_files(&config_dir, &config.include, &mut problems) {
+            let file = sources.len();
+            let name = path
+                .strip_prefix(&config_dir)
+                .unwrap_or(&path)
+                .display()
+                .to_string();
+            let format = Format::of(&path).unwrap_or(Format::Json);
+
+            let text = match std::fs::read_to_string(&path) {
+                Ok(text) => text,
+                Err(e) => {
+                    problems.push(
+                        Problem::error(String::new(), format!("could not be read: {}", e))
+                            .in_file(file),
+                    );
+                    sources.push(diagnostics::Source {
+                        name,
+                        text: String::new(),
+                        format,
+                    });
+                    continue;
+                }
+            };
+
+            let start = problems.len();
+            let include: Option<Include> = parse(&text, format, &mut problems);
+            for problem in &mut problems[start..] {
+                problem.file = file;
+            }
+            if let Some(include) = include {
+                menu_sources.push((file, include.menus));
+            }
+            sources.push(diagnostics::Source { name, text, format });
+        }
+
+        let (new_menus, origins) =
+            generate_menus(&config.global, menu_sources, &sources, &mut problems);
+
+        let mut menus = menus_state.lock().unwrap();
+        first_load = menus.is_empty();
+
+        if first_load {
+            let (registered, hotkey_problems) = register_hotkeys(app, new_menus, &origins);
+            *menus = registered;
+            problems.extend(hotkey_problems);
+        } else if !problems.iter().any(Problem::is_error) {
+            let hotkey_problems = swap_hotkeys(app, &menus, &new_menus, &origins);
+            if hotkey_problems.is_empty() {
+                *menus = new_menus;
+
+                // Only closed once replaced, so a failed reload leaves it open
+                if *app.state::<Mutex<usize>>().lock().unwrap() != usize::MAX {
+                    menu::close(app.clone());
+                }
+            }
+            problems.extend(hotkey_problems);
+        }
+    } else {
+        first_load = menus_state.lock().unwrap().is_empty();
+    }
+
+    if problems.is_empty() {
+        return Ok(());
+    }
+
+    let failed = problems.iter().any(Problem::is_error);
+    let message = format!(
+        "{}\n{}",
+        if failed && first_load {
+            "Config invalid, only the valid menus were loaded:"
+        } else if failed {
+            "Config invalid, keeping the previous menus:"
+        } else {
+            "Config loaded with warnings:"
+        },
+        diagnostics::describe(&sources, &problems)
+    );
+    crate::error(app, message.clone());
+
+    if failed {
+        Err(message)
+    } else {
+        Ok(())
+    }
+}
+
+/// The paths to watch for `files`: each file, where it links to, and the
+/// directories holding them outside the config directory, which is watched
+/// as a whole.
+fn watched_paths(config_dir: &Path, files: &[PathBuf]) -> (Vec<PathBuf>, Vec<PathBuf>) {
+    let mut paths = files.to_vec();
+    paths.extend(
+        files
+            .iter()
+            .filter_map(|file| std::fs::canonicalize(file).ok())
+            .filter(|target| !files.contains(target)),
+    );
+
+    let mut dirs: Vec<PathBuf> = Vec::new();
+    for dir in paths.iter().filter_map(|path| path.parent()) {
+        if !dir.starts_with(config_dir) && !dirs.iter().any(|other| other == dir) {
+            dirs.push(dir.to_path_buf());
+        }
+    }
+
+    (paths, dirs)
+}
+
+/// Reloads the config whenever the config file or a file it includes changes,
+/// including when included files are added or removed.
 pub fn start_listening(app_handle: &AppHandle) {
     let app = app_handle.clone();
 
@@ -357,16 +1361,12 @@ pub fn start_listening(app_handle: &AppHandle) {
         .path()
         .config_dir()
         .expect("Could not get config directory")
-        .join("quick-find/");
-    let config_path = config_dir.join("config.json");
+        .join("quick-find");
 
     std::thread::spawn(move || {
-        let menus = app.state::<Mutex<Vec<crate::Menu>>>();
         let (tx, rx) = channel();
 
-        if let Ok(config) = load(&app, &config_dir) {
-            generate_menus(&app, menus.lock().unwrap(), config);
-        }
+        let _ = reload(&app);
 
         let mut watcher: RecommendedWatcher = Watcher::new(
             tx,
@@ -375,22 +1375,61 @@ pub fn start_listening(app_handle: &AppHandle) {
         .expect("failed to create watcher");
 
         watcher
-            .watch(config_path.as_path(), RecursiveMode::NonRecursive)
-            .expect("failed to watch file");
+            .watch(config_dir.as_path(), RecursiveMode::Recursive)
+            .expect("failed to watch config directory");
+
+        let (mut paths, mut dirs) = watched_paths(&config_dir, &config_files(&config_dir));
+        for dir in &dirs {
+            if let Err(e) = watcher.watch(dir, RecursiveMode::NonRecursive) {
+                eprintln!("Could not watch {}: {}", dir.display(), e);
+            }
+        }
 
         loop {
             match rx.recv() {
-                Ok(_) => {
-                    println!("Config file changed");
-                    if let Ok(config) = load(&app, &config_dir) {
-                        if *app.state::<Mutex<usize>>().lock().unwrap() != usize::MAX {
-                            menu::close(app.clone());
+                Ok(Ok(event)) => {
+                    let (new_paths, new_dirs) =
+                        watched_paths(&config_dir, &config_files(&config_dir));
+
+                    if event
+                        .paths
+                        .iter()
+                        .any(|path| paths.contains(path) || new_paths.contains(path))
+                    {
+                        eprintln!("Config file changed");
+                        let _ = reload(&app);
+                    }
+
+                    for dir in dirs.iter().filter(|&dir| !new_dirs.contains(dir)) {
+                        let _ = watcher.unwatch(dir);
+                    }
+                    for dir in new_dirs.iter().filter(|&dir| !dirs.contains(dir)) {
+                        if let Err(e) = watcher.watch(dir, RecursiveMode::NonRecursive) {
+                            eprintln!("Could not watch {}: {}", dir.display(), e);
                         }
-                        generate_menus(&app, menus.lock().unwrap(), config);
                     }
+                    paths = new_paths;
+                    dirs = new_dirs;
                 }
-                Err(e) => println!("Watch error: {:?}", e),
+                Ok(Err(e)) => eprintln!("Watch error: {:?}", e),
+                Err(e) => eprintln!("Watch error: {:?}", e),
             }
         }
     });
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn schema_matches_documented_schema() {
+        let generated: serde_json::Value = serde_json::from_str(&schema()).unwrap();
+        let documented: serde_json::Value =
+            serde_json::from_str(include_str!("../../doc/config.schema.json")).unwrap();
+        assert_eq!(
+            generated, documented,
+            "doc/config.schema.json is out of date, regenerate it with `quick-find schema`"
+        );
+    }
+}
@@ -0,0 +1,619 @@
+use std::fmt::Write;
+
+use crate::config::Format;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Severity {
+    Error,
+    Warning,
+}
+
+/// A problem found in the config, at a JSON path such as
+/// `menus[1].entries[0]`.
+#[derive(Debug, Clone)]
+pub struct Problem {
+    pub severity: Severity,
+    /// The index of the file the problem is in, where 0 is the config file.
+    pub file: usize,
+    pub path: String,
+    pub message: String,
+    /// Where the problem is, when known better than the path's value.
+    pub position: Option<(usize, usize)>,
+}
+
+impl Problem {
+    pub fn error(path: String, message: String) -> Self {
+        Problem {
+            severity: Severity::Error,
+            file: 0,
+            path,
+            message,
+            position: None,
+        }
+    }
+
+    pub fn warning(path: String, message: String) -> Self {
+        Problem {
+            severity: Severity::Warning,
+            file: 0,
+            path,
+            message,
+            position: None,
+        }
+    }
+
+    pub fn in_file(self, file: usize) -> Self {
+        Problem { file, ..self }
+    }
+
+    pub fn is_error(&self) -> bool {
+        self.severity == Severity::Error
+    }
+}
+
+/// A config file, named relative to the config directory.
+pub struct Source {
+    pub name: String,
+    pub text: String,
+    pub format: Format,
+}
+
+/// Lists every problem on its own line, errors first, with its file and the
+/// line and column of its value where it can be found. `sources` holds every
+/// file, by index.
+pub fn describe(sources: &[Source], problems: &[Problem]) -> String {
+    let mut sorted: Vec<&Problem> = problems.iter().collect();
+    sorted.sort_by_key(|problem| !problem.is_error());
+
+    let mut description = String::new();
+    for problem in sorted {
+        let severity = match problem.severity {
+            Severity::Error => "error",
+            Severity::Warning => "warning",
+        };
+        let _ = write!(description, "{}: ", severity);
+
+        let source = sources.get(problem.file);
+        if let Some(source) = source {
+            let _ = write!(description, "{}: ", source.name);
+        }
+        if !problem.path.is_empty() {
+            let _ = write!(description, "{}", problem.path);
+        }
+        let position = problem.position.or_else(|| {
+            source
+                .filter(|_| !problem.path.is_empty())
+                .and_then(|source| locate(&source.text, source.format, &problem.path))
+        });
+        if let Some((line, column)) = position {
+            let _ = write!(description, " (line {}, column {})", line, column);
+        }
+        if !problem.path.is_empty() || position.is_some() {
+            description.push_str(": ");
+        }
+        let _ = writeln!(description, "{}", problem.message);
+    }
+
+    description.trim_end().to_string()
+}
+
+/// Turns a path from `serde_path_to_error` into the form used by problems.
+pub fn error_path(path: &serde_path_to_error::Path) -> String {
+    let mut formatted = String::new();
+
+    for segment in path.iter() {
+        match segment {
+            serde_path_to_error::Segment::Seq { index } => {
+                let _ = write!(formatted, "[{}]", index);
+            }
+            serde_path_to_error::Segment::Map { key } => {
+                if !formatted.is_empty() {
+                    formatted.push('.');
+                }
+                formatted.push_str(key);
+            }
+            _ => {}
+        }
+    }
+
+    formatted
+}
+
+/// Turns a path from `serde_ignored` into the form used by problems.
+pub fn ignored_path(path: &serde_ignored::Path) -> String {
+    match path {
+        serde_ignored::Path::Root => String::new(),
+        serde_ignored::Path::Seq { parent, index } => {
+            format!("{}[{}]", ignored_path(parent), index)
+        }
+        serde_ignored::Path::Map { parent, key } => {
+            let parent = ignored_path(parent);
+            if parent.is_empty() {
+                key.clone()
+            } else {
+                format!("{}.{}", parent, key)
+            }
+        }
+        serde_ignored::Path::Some { parent }
+        | serde_ignored::Path::NewtypeStruct { parent }
+        | serde_ignored::Path::NewtypeVariant { parent } => ignored_path(parent),
+    }
+}
+
+enum Segment<'a> {
+    Key(&'a str),
+    Index(usize),
+}
+
+fn parse_path(path: &str) -> Vec<Segment<'_>> {
+    let mut segments = Vec::new();
+
+    for part in path.split('.').filter(|part| !part.is_empty()) {
+        let (key, indices) = part.split_once('[').unwrap_or((part, ""));
+        if !key.is_empty() {
+            segments.push(Segment::Key(key));
+        }
+        for index in indices.split('[') {
+            if let Ok(index) = index.trim_end_matches(']').parse() {
+                segments.push(Segment::Index(index));
+            }
+        }
+    }
+
+    segments
+}
+
+/// A minimal JSON reader that only keeps track of where it is, used to find
+/// where a value starts.
+struct Scanner<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+}
+
+impl Scanner<'_> {
+    fn peek(&self) -> Option<u8> {
+        self.bytes.get(self.pos).copied()
+    }
+
+    fn skip_whitespace(&mut self) {
+        while matches!(self.peek(), Some(b' ' | b'\t' | b'\n' | b'\r')) {
+            self.pos += 1;
+        }
+    }
+
+    fn expect(&mut self, byte: u8) -> Option<()> {
+        self.skip_whitespace();
+        if self.peek() != Some(byte) {
+            return None;
+        }
+        self.pos += 1;
+        Some(())
+    }
+
+    fn string(&mut self) -> Option<String> {
+        let start = self.pos;
+        self.expect(b'"')?;
+
+        loop {
+            match self.peek()? {
+                b'\\' => self.pos += 2,
+                b'"' => break,
+                _ => self.pos += 1,
+            }
+        }
+        self.pos += 1;
+
+        serde_json::from_slice(self.bytes.get(start..self.pos)?).ok()
+    }
+
+    fn skip_value(&mut self) -> Option<()> {
+        self.skip_whitespace();
+
+        match self.peek()? {
+            b'"' => {
+                self.string()?;
+            }
+            open @ (b'{' | b'[') => {
+                self.pos += 1;
+                loop {
+                    self.skip_whitespace();
+                    match self.peek()? {
+                        b'}' | b']' => {
+                            self.pos += 1;
+                            break;
+                        }
+                        b',' => self.pos += 1,
+                        _ => {
+                            if open == b'{' {
+                                self.string()?;
+                                self.expect(b':')?;
+                            }
+                            self.skip_value()?;
+                        }
+                    }
+                }
+            }
+            _ => {
+                while !matches!(
+                    self.peek(),
+                    None | Some(b',' | b'}' | b']' | b' ' | b'\t' | b'\n' | b'\r')
+                ) {
+                    self.pos += 1;
+                }
+            }
+        }
+
+        Some(())
+    }
+
+    fn enter(&mut self, segment: &Segment) -> Option<()> {
+        match segment {
+            Segment::Key(key) => {
+                self.expect(b'{')?;
+                loop {
+                    self.skip_whitespace();
+                    if self.peek()? == b'}' {
+                        return None;
+                    }
+
+                    let found = self.string()? == *key;
+                    self.expect(b':')?;
+                    if found {
+                        return Some(());
+                    }
+
+                    self.skip_value()?;
+                    self.skip_whitespace();
+                    if self.peek()? == b',' {
+                        self.pos += 1;
+                    }
+                }
+            }
+            Segment::Index(index) => {
+                self.expect(b'[')?;
+                for _ in 0..*index {
+                    self.skip_value()?;
+                    self.expect(b',')?;
+                }
+                Some(())
+            }
+        }
+    }
+}
+
+/// Finds the line and column, both from 1, where the value at `path` starts
+/// in `text`, written in `format`.
+fn locate(text: &str, format: Format, path: &str) -> Option<(usize, usize)> {
+    match format {
+        Format::Json => locate_json(text, path),
+        Format::Toml => locate_toml(text, path),
+        Format::Yaml => locate_yaml(text, path),
+    }
+}
+
+fn locate_json(text: &str, path: &str) -> Option<(usize, usize)> {
+    let mut scanner = Scanner {
+        bytes: text.as_bytes(),
+        pos: 0,
+    };
+
+    for segment in parse_path(path) {
+        scanner.enter(&segment)?;
+    }
+    scanner.skip_whitespace();
+
+    Some(position(text, scanner.pos))
+}
+
+fn locate_toml(text: &str, path: &str) -> Option<(usize, usize)> {
+    let document = toml_edit::ImDocument::parse(text).ok()?;
+    let mut item = document.as_item();
+
+    for segment in parse_path(path) {
+        item = match segment {
+            Segment::Key(key) => item.get(key)?,
+            Segment::Index(index) => item.get(index)?,
+        };
+    }
+
+    Some(position(text, item.span()?.start))
+}
+
+/// Where a YAML value starts: its line, the byte column after its key or
+/// dash, and the indentation of that key or dash, which is `None` at the
+/// root.
+struct YamlCursor {
+    line: usize,
+    column: usize,
+    parent: Option<usize>,
+}
+
+/// Only block style YAML is followed, by indentation, so values inside flow
+/// collections like `[a, b]` aren't found.
+fn locate_yaml(text: &str, path: &str) -> Option<(usize, usize)> {
+    let mut start = 0;
+    let lines: Vec<(usize, &str)> = text
+        .split('\n')
+        .map(|line| {
+            let line_start = start;
+            start += line.len() + 1;
+            (line_start, line.trim_end_matches('\r'))
+        })
+        .collect();
+
+    let mut cursor = YamlCursor {
+        line: (0..lines.len()).find(|i| is_yaml_content(lines[*i].1))?,
+        column: 0,
+        parent: None,
+    };
+    for segment in parse_path(path) {
+        cursor = enter_yaml(&lines, &cursor, &segment)?;
+    }
+
+    let (line, column) = yaml_value_start(&lines, &cursor)?;
+    Some(position(text, lines[line].0 + column))
+}
+
+fn is_yaml_content(line: &str) -> bool {
+    let trimmed = line.trim_start();
+    !trimmed.is_empty() && !trimmed.starts_with('#') && trimmed != "---"
+}
+
+fn is_yaml_item(text: &str) -> bool {
+    text == "-" || text.starts_with("- ")
+}
+
+fn indentation(line: &str) -> usize {
+    line.len() - line.trim_start_matches(' ').len()
+}
+
+/// The line and column where the value at `cursor` starts, which is on the
+/// next line if nothing follows its key or dash.
+fn yaml_value_start(lines: &[(usize, &str)], cursor: &YamlCursor) -> Option<(usize, usize)> {
+    let rest = lines[cursor.line].1.get(cursor.column..)?;
+    let trimmed = rest.trim_start_matches(' ');
+    if !trimmed.is_empty() && !trimmed.starts_with('#') {
+        return Some((cursor.line, cursor.column + rest.len() - trimmed.len()));
+    }
+
+    let line = (cursor.line + 1..lines.len()).find(|i| is_yaml_content(lines[*i].1))?;
+    let text = lines[line].1;
+    let indent = indentation(text);
+    // A list may be indented as far as the key it belongs to
+    let nested = cursor.parent.is_none_or(|parent| {
+        indent > parent || (indent == parent && is_yaml_item(&text[indent..]))
+    });
+
+    nested.then_some((line, indent))
+}
+
+/// Moves from the collection at `cursor` to its value at `segment`.
+fn enter_yaml(
+    lines: &[(usize, &str)],
+    cursor: &YamlCursor,
+    segment: &Segment,
+) -> Option<YamlCursor> {
+    let (first, indent) = yaml_value_start(lines, cursor)?;
+    let mut index = 0;
+
+    for (line, (_, text)) in lines.iter().enumerate().skip(first) {
+        if line > first && (!is_yaml_content(text) || indentation(text) > indent) {
+            continue;
+        }
+        if line > first && indentation(text) < indent {
+            return None;
+        }
+        let entry = &text[indent..];
+
+        match segment {
+            Segment::Key(key) => {
+                // Items of a list under an earlier key at this indentation
+                if is_yaml_item(entry) {
+                    continue;
+                }
+                let (found, colon) = yaml_key(entry)?;
+                if found == *key {
+                    return Some(YamlCursor {
+                        line,
+                        column: indent + colon + 1,
+                        parent: Some(indent),
+                    });
+                }
+            }
+            Segment::Index(i) => {
+                if !is_yaml_item(entry) {
+                    return None;
+                }
+                if index == *i {
+                    return Some(YamlCursor {
+                        line,
+                        column: indent + 1,
+                        parent: Some(indent),
+                    });
+                }
+                index += 1;
+            }
+        }
+    }
+
+    None
+}
+
+/// The key a mapping entry starts with, unquoted, and the byte index of the
+/// colon after it.
+fn yaml_key(entry: &str) -> Option<(&str, usize)> {
+    let (key, after) = match entry.chars().next()? {
+        quote @ ('"' | '\'') => {
+            let end = entry[1..].find(quote)? + 1;
+            (&entry[1..end], end + 1)
+        }
+        _ => {
+            let colon = entry
+                .char_indices()
+                .find(|(i, c)| {
+                    *c == ':' && matches!(entry[i + 1..].chars().next(), None | Some(' '))
+                })?
+                .0;
+            (entry[..colon].trim_end(), colon)
+        }
+    };
+
+    let colon = after + entry[after..].find(':')?;
+    Some((key, colon))
+}
+
+/// The line and column, both from 1, of the byte at `index` in `text`.
+pub fn position(text: &str, index: usize) -> (usize, usize) {
+    let before = text.get(..index).unwrap_or(text);
+    let line = before.matches('\n').count() + 1;
+    let column = before
+        .rsplit('\n')
+        .next()
+        .unwrap_or_default()
+        .chars()
+        .count()
+        + 1;
+
+    (line, column)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The position of `needle` in single line `text`, counted by hand.
+    fn column_of(text: &str, needle: &str) -> Option<(usize, usize)> {
+        let index = text.find(needle).unwrap();
+        Some((1, text[..index].chars().count() + 1))
+    }
+
+    #[test]
+    fn locate_finds_values_across_lines() {
+        let text = "{\n  \"menus\": [\n    {\"hotkey\": \"x\"}\n  ]\n}";
+
+        assert_eq!(locate_json(text, "menus"), Some((2, 12)));
+        assert_eq!(locate_json(text, "menus[0]"), Some((3, 5)));
+        assert_eq!(locate_json(text, "menus[0].hotkey"), Some((3, 16)));
+    }
+
+    #[test]
+    fn locate_descends_nested_arrays() {
+        let text = r#"{"a": [[1, 2], [3, [4, 5]]], "b": [{"c": []}, {"c": [6]}]}"#;
+
+        assert_eq!(locate_json(text, "a[1][1][0]"), column_of(text, "4"));
+        assert_eq!(locate_json(text, "a[1][1][1]"), column_of(text, "5"));
+        assert_eq!(locate_json(text, "b[1].c[0]"), column_of(text, "6"));
+    }
+
+    #[test]
+    fn locate_skips_escaped_strings() {
+        let text = r#"{"a\"b": "x\\", "c": "]}\",[", "d": 1}"#;
+
+        assert_eq!(locate_json(text, "d"), column_of(text, "1}"));
+        assert_eq!(locate_json(text, "a\"b"), column_of(text, r#""x\\""#));
+    }
+
+    #[test]
+    fn locate_counts_columns_in_characters() {
+        let text = r#"{"é": "ü", "b": 2}"#;
+
+        assert_eq!(locate_json(text, "b"), Some((1, 17)));
+    }
+
+    #[test]
+    fn locate_misses_paths_not_in_the_text() {
+        let text = r#"{"menus": [{"hotkey": "x"}], "d": 1}"#;
+
+        assert_eq!(locate_json(text, "missing"), None);
+        assert_eq!(locate_json(text, "menus[1]"), None);
+        assert_eq!(locate_json(text, "menus[0].name"), None);
+        assert_eq!(locate_json(text, "d.e"), None);
+        assert_eq!(locate_json(text, "d[0]"), None);
+        assert_eq!(locate_json("{", "d"), None);
+    }
+
+    #[test]
+    fn locate_finds_values_in_toml() {
+        let text = concat!(
+            "[global]\n",
+            "allowed_chars = \"ab\"\n",
+            "\n",
+            "[[menus]]\n",
+            "hotkey = \"x\"\n",
+            "\n",
+            "[[menus]]\n",
+            "name = \"y\"\n",
+            "entries = [\"a\", \"b\"]\n",
+        );
+
+        assert_eq!(
+            locate(text, Format::Toml, "global.allowed_chars"),
+            Some((2, 17))
+        );
+        assert_eq!(locate(text, Format::Toml, "menus[1].name"), Some((8, 8)));
+        assert_eq!(
+            locate(text, Format::Toml, "menus[1].entries[1]"),
+            Some((9, 17))
+        );
+        assert_eq!(locate(text, Format::Toml, "menus[2]"), None);
+        assert_eq!(locate(text, Format::Toml, "global.missing"), None);
+    }
+
+    #[test]
+    fn locate_finds_values_in_yaml() {
+        let text = concat!(
+            "global:\n",
+            "  allowed_chars: ab\n",
+            "menus:\n",
+            "- hotkey: x\n",
+            "  action: open\n",
+            "- name: y\n",
+            "  entries:\n",
+            "    - a\n",
+            "    - \"b\"\n",
+        );
+
+        assert_eq!(
+            locate(text, Format::Yaml, "global.allowed_chars"),
+            Some((2, 18))
+        );
+        assert_eq!(locate(text, Format::Yaml, "menus[0].action"), Some((5, 11)));
+        assert_eq!(locate(text, Format::Yaml, "menus[1]"), Some((6, 3)));
+        assert_eq!(locate(text, Format::Yaml, "menus[1].name"), Some((6, 9)));
+        assert_eq!(
+            locate(text, Format::Yaml, "menus[1].entries[1]"),
+            Some((9, 7))
+        );
+        assert_eq!(locate(text, Format::Yaml, "menus[2]"), None);
+        assert_eq!(locate(text, Format::Yaml, "global.missing"), None);
+        assert_eq!(locate(text, Format::Yaml, "menus[0].hotkey.x"), None);
+    }
+
+    #[test]
+    fn locate_skips_yaml_comments_and_quotes() {
+        let text = "# menus\n\"menus\":\n  # the first\n  - 'hotkey': x\n";
+
+        assert_eq!(locate(text, Format::Yaml, "menus[0].hotkey"), Some((4, 15)));
+    }
+
+    #[test]
+    fn describe_lists_errors_first_with_their_file() {
+        let sources = [Source {
+            name: "config.json".to_string(),
+            text: r#"{"menus": [{"hotkey": "x"}]}"#.to_string(),
+            format: Format::Json,
+        }];
+        let problems = [
+            Problem::warning("menus".to_string(), "a warning".to_string()),
+            Problem::error("menus[0].hotkey".to_string(), "an error".to_string()),
+        ];
+
+        assert_eq!(
+            describe(&sources, &problems),
+            concat!(
+                "error: config.json: menus[0].hotkey (line 1, column 23): an error\n",
+                "warning: config.json: menus (line 1, column 11): a warning"
+            )
+        );
+    }
+}
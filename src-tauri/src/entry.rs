@@ -1,17 +1,128 @@
-use std::process::Command;
+use std::{
+    collections::HashMap,
+    io::Write,
+    path::Path,
+    process::{Command, Stdio},
+};
 
+use caseless::default_case_fold_str;
 use regex::Regex;
 use tauri::AppHandle;
+use tauri_plugin_clipboard_manager::ClipboardExt;
 use tauri_plugin_opener::OpenerExt;
+use unicode_normalization::{char::is_combining_mark, UnicodeNormalization};
+use unicode_segmentation::UnicodeSegmentation;
+
+use crate::config::CommandTemplate;
+
+/// Builds a command that runs `cmd` through the platform's shell.
+pub fn shell_command(cmd: &str) -> Command {
+    #[cfg(target_os = "windows")]
+    {
+        let mut command = Command::new("cmd");
+        command.args(["/C", cmd]);
+        command
+    }
+
+    #[cfg(not(target_os = "windows"))]
+    {
+        let mut command = Command::new("sh");
+        command.arg("-c").arg(cmd);
+        command
+    }
+}
+
+/// Quotes `value` so the shell passes it through as a single argument. On
+/// Unix it is passed literally. cmd.exe has no way to escape `%` inside
+/// quotes, so `%VAR%` in `value` is still expanded there, as is `!VAR!` when
+/// delayed expansion is enabled.
+pub fn shell_quote(value: &str) -> String {
+    #[cfg(target_os = "windows")]
+    {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    }
+
+    #[cfg(not(target_os = "windows"))]
+    {
+        format!("'{}'", value.replace('\'', "'\\''"))
+    }
+}
+
+/// Replaces the placeholders in a command template. Braces that don't form a
+/// known placeholder are kept as they are, and `{{` and `}}` produce literal
+/// braces.
+fn render(template: &str, entry: &Entry, menu: Option<&str>, quote: bool) -> String {
+    let mut result = String::new();
+    let mut rest = template;
+
+    while let Some(i) = rest.find(['{', '}']) {
+        result.push_str(&rest[..i]);
+        rest = &rest[i..];
+
+        if rest.starts_with("{{") || rest.starts_with("}}") {
+            result.push_str(&rest[..1]);
+            rest = &rest[2..];
+            continue;
+        }
+
+        if let Some(end) = rest.find('}').filter(|_| rest.starts_with('{')) {
+            if let Some(value) = placeholder(&rest[1..end], entry, menu) {
+                if quote {
+                    result.push_str(&shell_quote(&value));
+                } else {
+                    result.push_str(&value);
+                }
+                rest = &rest[end + 1..];
+                continue;
+            }
+        }
+
+        result.push_str(&rest[..1]);
+        rest = &rest[1..];
+    }
+    result.push_str(rest);
+
+    result
+}
+
+fn placeholder(key: &str, entry: &Entry, menu: Option<&str>) -> Option<String> {
+    let path = Path::new(&entry.full_string);
+    let lossy = |s: &std::ffi::OsStr| s.to_string_lossy().into_owned();
+
+    match key {
+        "" | "path" => Some(entry.full_string.clone()),
+        "name" => Some(path.file_name().map_or(entry.full_string.clone(), lossy)),
+        "stem" => Some(path.file_stem().map_or(entry.full_string.clone(), lossy)),
+        "ext" => Some(path.extension().map(lossy).unwrap_or_default()),
+        "dir" => Some(
+            path.parent()
+                .map(|p| p.to_string_lossy().into_owned())
+                .unwrap_or_default(),
+        ),
+        "display" => Some(entry.string.clone()),
+        "menu" => Some(menu.unwrap_or_default().to_string()),
+        _ => key
+            .strip_prefix("env:")
+            .map(|var| std::env::var(var).unwrap_or_default()),
+    }
+}
 
 #[derive(Clone)]
 pub enum ActionType {
     Open,
-    Command(String),
+    Command {
+        template: CommandTemplate,
+        quote: bool,
+    },
+    Reveal,
+    Copy,
+    Print,
 }
 
 impl ActionType {
-    pub fn activate(&self, app_handle: &AppHandle, string: &str) {
+    pub fn activate(&self, app_handle: &AppHandle, entry: &Entry, menu: Option<&str>) {
+        let string = entry.full_string.as_str();
+
         match self {
             ActionType::Open => {
                 let result = app_handle.opener().open_path(string, None::<&str>);
@@ -20,41 +131,159 @@ impl ActionType {
                     crate::error(app_handle, format!("Could not open file: {}", string));
                 }
             }
-            ActionType::Command(cmd) => {
-                #[cfg(target_os = "windows")]
-                let _output = Command::new("cmd")
-                    .args(["/C", &cmd.replace("{}", string)])
-                    .output();
-
-                #[cfg(not(target_os = "windows"))]
-                let _output = Command::new("sh")
-                    .arg("-c")
-                    .arg(cmd.replace("{}", string))
-                    .output();
+            ActionType::Command { template, quote } => {
+                let mut command = match template {
+                    CommandTemplate::Shell(cmd) => shell_command(&render(cmd, entry, menu, *quote)),
+                    CommandTemplate::Argv(argv) => {
+                        let Some((program, args)) = argv.split_first() else {
+                            crate::error(app_handle, "Command is empty".to_string());
+                            return;
+                        };
+
+                        let mut command = Command::new(render(program, entry, menu, false));
+                        command.args(args.iter().map(|arg| render(arg, entry, menu, false)));
+                        command
+                    }
+                };
+                let command_string = format!("{:?}", command);
+
+                // Waited on in the background so a long-running command doesn't hold the menus
+                let child = command
+                    .stdin(Stdio::null())
+                    .stdout(Stdio::null())
+                    .stderr(Stdio::piped())
+                    .spawn();
+
+                match child {
+                    Ok(child) => {
+                        let app_handle = app_handle.clone();
+                        std::thread::spawn(move || match child.wait_with_output() {
+                            Ok(output) if !output.status.success() => crate::error(
+                                &app_handle,
+                                format!(
+                                    "Command {} exited with {}\n{}",
+                                    command_string,
+                                    output.status,
+                                    String::from_utf8_lossy(&output.stderr).trim_end()
+                                ),
+                            ),
+                            Ok(_) => {}
+                            Err(e) => crate::error(
+                                &app_handle,
+                                format!("Could not wait for command {}: {}", command_string, e),
+                            ),
+                        });
+                    }
+                    Err(e) => crate::error(
+                        app_handle,
+                        format!("Could not run command {}: {}", command_string, e),
+                    ),
+                }
             }
+            ActionType::Reveal => {
+                if app_handle.opener().reveal_item_in_dir(string).is_err() {
+                    crate::error(app_handle, format!("Could not reveal file: {}", string));
+                }
+            }
+            ActionType::Copy => {
+                if app_handle.clipboard().write_text(string).is_err() {
+                    crate::error(
+                        app_handle,
+                        format!("Could not copy to clipboard: {}", string),
+                    );
+                }
+            }
+            ActionType::Print => {
+                let mut stdout = std::io::stdout();
+                writeln!(stdout, "{}", string).expect("Could not write selection");
+                stdout.flush().expect("Could not flush selection");
+                app_handle.exit(0);
+            }
+        }
+    }
+}
+
+/// How selection letters are ranked when several could be chosen.
+#[derive(Clone, Default)]
+pub struct Scoring {
+    pub prefer_word_starts: bool,
+    /// The effort of typing each lowercase character, empty when no keyboard
+    /// layout is set.
+    pub key_costs: HashMap<char, usize>,
+    pub missing_key_cost: usize,
+}
+
+impl Scoring {
+    fn key_cost(&self, c: char) -> usize {
+        if self.key_costs.is_empty() {
+            return 0;
         }
+
+        match_key(c, false, false)
+            .chars()
+            .next()
+            .and_then(|c| self.key_costs.get(&c).copied())
+            .unwrap_or(self.missing_key_cost)
     }
 }
 
-#[derive(serde::Serialize, Clone)]
+#[derive(Clone)]
 pub struct Entry {
     pub string: String,
+    /// Byte offset of the selection letter in `string`.
     pub selection_index: usize,
-
-    #[serde(skip_serializing)]
+    /// The part of the entry's label left to type in hint mode.
+    pub hint: Option<String>,
     pub full_string: String,
-
-    #[serde(skip_serializing)]
     pub selection_letter: char,
-
-    #[serde(skip_serializing)]
+    /// Byte offset in `string` that selections are searched from.
     pub pos: usize,
-
-    #[serde(skip_serializing)]
     pub action: ActionType,
+    pub is_dir: bool,
+    pub preferred_index: Option<usize>,
+    pub hint_label: Option<String>,
+}
+
+/// What the frontend receives for an entry. The text is split around the
+/// selection's grapheme cluster, so it doesn't have to deal with byte
+/// offsets.
+#[derive(serde::Serialize)]
+struct EntryView<'a> {
+    string: &'a str,
+    pre: &'a str,
+    current: &'a str,
+    post: &'a str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    hint: Option<&'a str>,
+}
+
+impl serde::Serialize for Entry {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let (pre, current, post) = self.segments();
+
+        EntryView {
+            string: &self.string,
+            pre,
+            current,
+            post,
+            hint: self.hint.as_deref(),
+        }
+        .serialize(serializer)
+    }
 }
 
 impl Entry {
+    /// Splits `string` into the text before, of and after the grapheme
+    /// cluster holding the selection letter.
+    fn segments(&self) -> (&str, &str, &str) {
+        self.string
+            .grapheme_indices(true)
+            .find(|(start, g)| (*start..start + g.len()).contains(&self.selection_index))
+            .map_or(("", "", self.string.as_str()), |(start, g)| {
+                (&self.string[..start], g, &self.string[start + g.len()..])
+            })
+    }
+
     pub fn new(string: String, full_string: String, action: ActionType) -> Self {
         Self {
             string,
@@ -63,6 +292,10 @@ impl Entry {
             selection_index: usize::MAX,
             pos: 0,
             action,
+            is_dir: false,
+            preferred_index: None,
+            hint: None,
+            hint_label: None,
         }
     }
 
@@ -70,47 +303,23 @@ impl Entry {
         &mut self,
         allowed_chars: &str,
         allowed_regex: &Option<Regex>,
-        disallowed_chars: &str,
+        disallowed_chars: &[String],
         match_case: bool,
         match_selection_case: bool,
+        fold_diacritics: bool,
+        scoring: &Scoring,
     ) -> bool {
-        for (i, c) in self.string.char_indices().skip(self.pos) {
-            if c == ' ' {
+        for (i, c) in self.selection_order(scoring) {
+            if !is_selectable(
+                c,
+                allowed_chars,
+                allowed_regex,
+                disallowed_chars,
+                match_case,
+                match_selection_case,
+                fold_diacritics,
+            ) {
                 continue;
-            };
-
-            if !allowed_chars.is_empty() {
-                if match_case {
-                    if !allowed_chars.contains(c) {
-                        continue;
-                    }
-                } else {
-                    if !allowed_chars.contains(
-                        c.to_lowercase()
-                            .next()
-                            .expect("Could not convert allowed character to lowercase"),
-                    ) {
-                        continue;
-                    }
-                }
-            }
-            if allowed_regex.is_some() {
-                if !allowed_regex.as_ref().unwrap().is_match(&c.to_string()) {
-                    continue;
-                }
-            }
-            if match_selection_case {
-                if disallowed_chars.contains(c) {
-                    continue;
-                }
-            } else {
-                if disallowed_chars.contains(
-                    c.to_lowercase()
-                        .next()
-                        .expect("Could not convert disallowed character to lowercase"),
-                ) {
-                    continue;
-                }
             }
 
             self.selection_index = i;
@@ -120,4 +329,502 @@ impl Entry {
 
         return false;
     }
+
+    /// The grapheme clusters past `pos` in the order they should be tried as
+    /// the selection, best first. A cluster is selected by its first
+    /// character, so a combining mark is never offered on its own.
+    fn selection_order(&self, scoring: &Scoring) -> Vec<(usize, char)> {
+        let mut order = Vec::new();
+        let mut previous = None;
+
+        let mut offset = 0;
+
+        for (i, g) in self.string.grapheme_indices(true) {
+            let Some(c) = g.chars().next() else {
+                continue;
+            };
+            if i >= self.pos {
+                order.push((selection_score(offset, previous, c, scoring), i, c));
+                offset += 1;
+            }
+            previous = g.chars().last();
+        }
+
+        order.sort_by_key(|(score, _, _)| *score);
+        order.into_iter().map(|(_, i, c)| (i, c)).collect()
+    }
+
+    /// Selects the remembered character again, if it is still past `pos` and
+    /// selectable.
+    pub fn get_preferred_selection(
+        &mut self,
+        allowed_chars: &str,
+        allowed_regex: &Option<Regex>,
+        disallowed_chars: &[String],
+        match_case: bool,
+        match_selection_case: bool,
+        fold_diacritics: bool,
+    ) -> bool {
+        let Some(index) = self.preferred_index else {
+            return false;
+        };
+
+        let Some(c) = self.string.get(index..).and_then(|s| s.chars().next()) else {
+            return false;
+        };
+        if index < self.pos || !self.string.grapheme_indices(true).any(|(i, _)| i == index) {
+            return false;
+        }
+
+        if !is_selectable(
+            c,
+            allowed_chars,
+            allowed_regex,
+            disallowed_chars,
+            match_case,
+            match_selection_case,
+            fold_diacritics,
+        ) {
+            return false;
+        }
+
+        self.selection_index = index;
+        self.selection_letter = c;
+        true
+    }
+
+    /// Every selectable character past `pos`, with the remembered one first.
+    pub fn get_candidates(
+        &self,
+        allowed_chars: &str,
+        allowed_regex: &Option<Regex>,
+        disallowed_chars: &[String],
+        match_case: bool,
+        match_selection_case: bool,
+        fold_diacritics: bool,
+        scoring: &Scoring,
+    ) -> Vec<(usize, char)> {
+        let mut candidates: Vec<(usize, char)> = self
+            .selection_order(scoring)
+            .into_iter()
+            .filter(|(_, c)| {
+                is_selectable(
+                    *c,
+                    allowed_chars,
+                    allowed_regex,
+                    disallowed_chars,
+                    match_case,
+                    match_selection_case,
+                    fold_diacritics,
+                )
+            })
+            .collect();
+
+        if let Some(preferred) = candidates
+            .iter()
+            .position(|(i, _)| Some(*i) == self.preferred_index)
+        {
+            let candidate = candidates.remove(preferred);
+            candidates.insert(0, candidate);
+        }
+
+        candidates
+    }
+
+    pub fn clear_selection(&mut self) {
+        self.selection_index = usize::MAX;
+        self.selection_letter = char::MAX;
+    }
+
+    pub fn select(&mut self, (index, letter): (usize, char)) {
+        self.selection_index = index;
+        self.selection_letter = letter;
+    }
+
+    /// Moves `pos` past the grapheme cluster holding the selection letter.
+    pub fn step_past_selection(&mut self) {
+        let (pre, current, _) = self.segments();
+        self.pos = pre.len() + current.len();
+    }
+}
+
+/// Whether `c` starts a word: it follows a separator, is an uppercase letter
+/// after a lowercase one, or starts a number.
+fn is_word_start(previous: Option<char>, c: char) -> bool {
+    match previous {
+        None => true,
+        Some(p) => {
+            matches!(p, ' ' | '_' | '-' | '.' | '/' | '\\')
+                || (p.is_lowercase() && c.is_uppercase())
+                || (c.is_ascii_digit() && !p.is_ascii_digit())
+        }
+    }
+}
+
+/// Lower is better, and any word start beats any other character when they
+/// are preferred. `offset` is the distance from the first character that can
+/// still be selected.
+fn selection_score(
+    offset: usize,
+    previous: Option<char>,
+    c: char,
+    scoring: &Scoring,
+) -> (bool, usize) {
+    (
+        scoring.prefer_word_starts && !is_word_start(previous, c),
+        offset.saturating_add(scoring.key_cost(c)),
+    )
+}
+
+/// What `c` is matched by: case folded unless `match_case`, and without
+/// diacritics if `fold_diacritics`, so that `e` matches `É`. Some characters
+/// fold to several, such as `ß` to `ss`.
+pub fn match_key(c: char, match_case: bool, fold_diacritics: bool) -> String {
+    let key = if match_case {
+        c.to_string()
+    } else {
+        default_case_fold_str(&c.to_string())
+    };
+
+    if fold_diacritics {
+        key.nfd().filter(|c| !is_combining_mark(*c)).collect()
+    } else {
+        key
+    }
+}
+
+fn is_selectable(
+    c: char,
+    allowed_chars: &str,
+    allowed_regex: &Option<Regex>,
+    disallowed_chars: &[String],
+    match_case: bool,
+    match_selection_case: bool,
+    fold_diacritics: bool,
+) -> bool {
+    if c == ' ' {
+        return false;
+    };
+
+    if !allowed_chars.is_empty() {
+        let key = match_key(c, match_case, fold_diacritics);
+        if !allowed_chars
+            .chars()
+            .any(|allowed| match_key(allowed, match_case, fold_diacritics) == key)
+        {
+            return false;
+        }
+    }
+    if allowed_regex.is_some() {
+        if !allowed_regex.as_ref().unwrap().is_match(&c.to_string()) {
+            return false;
+        }
+    }
+    if disallowed_chars.contains(&match_key(c, match_selection_case, fold_diacritics)) {
+        return false;
+    }
+
+    true
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(full_string: &str) -> Entry {
+        Entry::new(
+            "shown".to_string(),
+            full_string.to_string(),
+            ActionType::Print,
+        )
+    }
+
+    #[test]
+    fn render_replaces_path_placeholders() {
+        let entry = entry("/home/me/notes.tar.gz");
+
+        assert_eq!(
+            render("{} {path}", &entry, None, false),
+            "/home/me/notes.tar.gz /home/me/notes.tar.gz"
+        );
+        assert_eq!(
+            render("{name}|{stem}|{ext}|{dir}", &entry, None, false),
+            "notes.tar.gz|notes.tar|gz|/home/me"
+        );
+        assert_eq!(
+            render("{display} in {menu}", &entry, Some("files"), false),
+            "shown in files"
+        );
+    }
+
+    #[test]
+    fn render_reads_environment_variables() {
+        std::env::set_var("QUICK_FIND_RENDER_TEST", "value");
+
+        assert_eq!(
+            render("{env:QUICK_FIND_RENDER_TEST}", &entry("a"), None, false),
+            "value"
+        );
+        assert_eq!(
+            render(
+                "{env:QUICK_FIND_RENDER_TEST_UNSET}",
+                &entry("a"),
+                None,
+                false
+            ),
+            ""
+        );
+    }
+
+    #[test]
+    fn render_keeps_escaped_and_unknown_braces() {
+        let entry = entry("a");
+
+        assert_eq!(render("{{{}}}", &entry, None, false), "{a}");
+        assert_eq!(
+            render("x {unknown} y", &entry, None, false),
+            "x {unknown} y"
+        );
+        assert_eq!(render("{ }", &entry, None, false), "{ }");
+        assert_eq!(render("open { and }", &entry, None, false), "open { and }");
+    }
+
+    #[test]
+    fn render_without_placeholders_is_unchanged() {
+        assert_eq!(render("ls -la", &entry("a"), None, true), "ls -la");
+    }
+
+    #[cfg(not(target_os = "windows"))]
+    #[test]
+    fn render_quotes_each_value() {
+        let entry = entry("it's $(here); ok");
+
+        assert_eq!(
+            render("echo {} {name}", &entry, None, true),
+            "echo 'it'\\''s $(here); ok' 'it'\\''s $(here); ok'"
+        );
+    }
+
+    fn shown(string: &str) -> Entry {
+        Entry::new(string.to_string(), string.to_string(), ActionType::Print)
+    }
+
+    #[test]
+    fn segments_split_around_the_selected_cluster() {
+        let mut entry = shown("cafe\u{301} menu");
+
+        entry.select((0, 'c'));
+        assert_eq!(entry.segments(), ("", "c", "afe\u{301} menu"));
+
+        entry.select((3, 'e'));
+        assert_eq!(entry.segments(), ("caf", "e\u{301}", " menu"));
+
+        entry.select((4, '\u{301}'));
+        assert_eq!(entry.segments(), ("caf", "e\u{301}", " menu"));
+
+        entry.clear_selection();
+        assert_eq!(entry.segments(), ("", "", "cafe\u{301} menu"));
+    }
+
+    #[test]
+    fn combining_marks_are_not_candidates() {
+        let mut entry = shown("e\u{301}te\u{301}");
+
+        let candidates =
+            entry.get_candidates("", &None, &[], false, false, true, &Scoring::default());
+        assert_eq!(candidates, vec![(0, 'e'), (3, 't'), (4, 'e')]);
+
+        entry.pos = 3;
+        let candidates =
+            entry.get_candidates("", &None, &[], false, false, true, &Scoring::default());
+        assert_eq!(candidates, vec![(3, 't'), (4, 'e')]);
+    }
+
+    #[test]
+    fn step_past_selection_skips_the_whole_cluster() {
+        let mut entry = shown("Re\u{301}sume\u{301}.pdf");
+
+        entry.select((1, 'e'));
+        entry.step_past_selection();
+        assert_eq!(entry.pos, 4);
+        assert!(entry.get_selection("", &None, &[], false, false, true, &Scoring::default()));
+        assert_eq!((entry.selection_index, entry.selection_letter), (4, 's'));
+
+        entry.select((7, 'e'));
+        entry.step_past_selection();
+        assert_eq!(entry.pos, 10);
+        assert!(entry.get_selection("", &None, &[], false, false, true, &Scoring::default()));
+        assert_eq!((entry.selection_index, entry.selection_letter), (10, '.'));
+    }
+
+    #[test]
+    fn preferred_selection_must_start_a_cluster() {
+        let mut entry = shown("e\u{301}x");
+
+        entry.preferred_index = Some(1);
+        assert!(!entry.get_preferred_selection("", &None, &[], false, false, false));
+
+        entry.preferred_index = Some(0);
+        assert!(entry.get_preferred_selection("", &None, &[], false, false, false));
+        assert_eq!(entry.selection_letter, 'e');
+    }
+
+    #[test]
+    fn match_key_folds_case_fully() {
+        assert_eq!(match_key('A', false, false), "a");
+        assert_eq!(match_key('A', true, false), "A");
+        assert_eq!(match_key('ß', false, false), "ss");
+        assert_eq!(match_key('ẞ', false, false), "ss");
+        assert_eq!(match_key('ß', true, false), "ß");
+    }
+
+    #[test]
+    fn match_key_folds_diacritics() {
+        assert_eq!(match_key('é', false, false), "é");
+        assert_eq!(match_key('é', false, true), "e");
+        assert_eq!(match_key('É', false, true), "e");
+        assert_eq!(match_key('É', true, true), "E");
+    }
+
+    #[test]
+    fn allowed_chars_match_folded_letters() {
+        assert!(is_selectable('é', "e", &None, &[], false, false, true));
+        assert!(is_selectable('É', "e", &None, &[], false, false, true));
+        assert!(!is_selectable('é', "e", &None, &[], false, false, false));
+        assert!(!is_selectable('É', "e", &None, &[], true, false, true));
+    }
+
+    #[test]
+    fn disallowed_chars_follow_match_selection_case() {
+        let disallowed = ["a".to_string()];
+
+        assert!(!is_selectable(
+            'A',
+            "",
+            &None,
+            &disallowed,
+            false,
+            false,
+            false
+        ));
+        assert!(is_selectable(
+            'A',
+            "",
+            &None,
+            &disallowed,
+            false,
+            true,
+            false
+        ));
+        assert!(!is_selectable(
+            'a',
+            "",
+            &None,
+            &disallowed,
+            false,
+            true,
+            false
+        ));
+    }
+
+    #[test]
+    fn selection_skips_letters_typed_like_a_used_one() {
+        let mut entry = shown("Éße");
+        let used = [match_key('e', false, true), match_key('S', false, true)];
+
+        assert!(entry.get_selection("", &None, &used, false, false, true, &Scoring::default()));
+        assert_eq!((entry.selection_index, entry.selection_letter), (2, 'ß'));
+
+        let used = [match_key('e', false, true), match_key('ß', false, true)];
+        assert!(!entry.get_selection("", &None, &used, false, false, true, &Scoring::default()));
+    }
+
+    #[test]
+    fn typed_letters_match_selections_like_disallowed_chars() {
+        // Filtering keeps the entries whose selection has the typed key, and
+        // a selection is disallowed once its key is used. Both must agree.
+        let letters = ['e', 'é', 'É', 'E', 'ß', 'ẞ', 's', 'x'];
+
+        for (match_selection_case, fold_diacritics) in
+            [(false, false), (false, true), (true, false), (true, true)]
+        {
+            for typed in letters {
+                let key = match_key(typed, match_selection_case, fold_diacritics);
+
+                for letter in letters {
+                    let filtered = match_key(letter, match_selection_case, fold_diacritics) == key;
+                    let disallowed = !is_selectable(
+                        letter,
+                        "",
+                        &None,
+                        &[key.clone()],
+                        false,
+                        match_selection_case,
+                        fold_diacritics,
+                    );
+
+                    assert_eq!(filtered, disallowed, "{typed} and {letter}");
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn word_starts_follow_separators() {
+        assert!(is_word_start(None, 'b'));
+        for separator in [' ', '_', '-', '.', '/', '\\'] {
+            assert!(is_word_start(Some(separator), 'r'));
+        }
+        assert!(!is_word_start(Some('u'), 'i'));
+    }
+
+    #[test]
+    fn word_starts_in_camel_case() {
+        assert!(is_word_start(Some('d'), 'R'));
+        assert!(!is_word_start(Some('D'), 'R'));
+        assert!(!is_word_start(Some('R'), 'e'));
+    }
+
+    #[test]
+    fn word_starts_at_numbers() {
+        assert!(is_word_start(Some('v'), '2'));
+        assert!(!is_word_start(Some('2'), '0'));
+        assert!(!is_word_start(Some('2'), 'x'));
+    }
+
+    #[test]
+    fn selection_score_ranks_word_starts_first() {
+        let scoring = Scoring {
+            prefer_word_starts: true,
+            ..Scoring::default()
+        };
+
+        assert!(
+            selection_score(9, Some('_'), 'r', &scoring)
+                < selection_score(1, Some('b'), 'u', &scoring)
+        );
+        assert!(
+            selection_score(1, Some('b'), 'u', &Scoring::default())
+                < selection_score(9, Some('_'), 'r', &Scoring::default())
+        );
+    }
+
+    #[test]
+    fn prefer_word_starts_picks_the_distinguishing_word() {
+        let scoring = Scoring {
+            prefer_word_starts: true,
+            ..Scoring::default()
+        };
+        let used = ["b".to_string()];
+        let mut release = shown("build_release.sh");
+        let mut debug = shown("build_debug.sh");
+
+        assert!(release.get_selection("", &None, &used, false, false, false, &scoring));
+        assert!(debug.get_selection("", &None, &used, false, false, false, &scoring));
+        assert_eq!(release.selection_letter, 'r');
+        assert_eq!(debug.selection_letter, 'd');
+
+        assert!(release.get_selection("", &None, &used, false, false, false, &Scoring::default()));
+        assert_eq!(release.selection_letter, 'u');
+    }
 }
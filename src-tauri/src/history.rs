@@ -0,0 +1,194 @@
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::{HashMap, HashSet},
+    path::{Path, PathBuf},
+    time::{SystemTime, UNIX_EPOCH},
+};
+use tauri::{AppHandle, Manager};
+
+/// How many entries of a menu the history keeps, dropping the least used
+/// beyond it.
+const MAX_HISTORY_ENTRIES: usize = 1000;
+
+/// How many entries of a menu assignments are kept for before those no longer
+/// listed are forgotten.
+const MAX_ASSIGNED_ENTRIES: usize = 1000;
+
+#[derive(Serialize, Deserialize, Default, Clone, Copy)]
+struct Usage {
+    count: u64,
+    last_used: u64,
+}
+
+impl Usage {
+    /// Weights the use count by how recently the entry was last used.
+    fn frecency(&self, now: u64) -> u64 {
+        let days = now.saturating_sub(self.last_used) / (60 * 60 * 24);
+        let weight = match days {
+            0..=3 => 100,
+            4..=13 => 70,
+            14..=30 => 50,
+            31..=89 => 30,
+            _ => 10,
+        };
+
+        self.count * weight
+    }
+}
+
+/// How often and how recently entries were activated, by menu and then by
+/// the entry's full string.
+pub struct History {
+    path: PathBuf,
+    menus: HashMap<String, HashMap<String, Usage>>,
+}
+
+fn data_path(app: &AppHandle, file_name: &str) -> PathBuf {
+    app.path()
+        .data_dir()
+        .expect("Could not get data directory")
+        .join("quick-find")
+        .join(file_name)
+}
+
+fn load_file<T: for<'de> Deserialize<'de> + Default>(path: &PathBuf) -> T {
+    match std::fs::read_to_string(path) {
+        Ok(text) => serde_json::from_str(&text).unwrap_or_else(|e| {
+            eprintln!("{} invalid, starting over: {}", path.display(), e);
+            T::default()
+        }),
+        Err(_) => T::default(),
+    }
+}
+
+/// Writes to a file beside `path` and renames it over `path`, so a crash
+/// while saving never leaves a truncated file behind.
+fn save_file<T: Serialize>(path: &Path, value: &T) -> std::io::Result<()> {
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir)?;
+    }
+
+    let temp_path = path.with_extension("json.tmp");
+    std::fs::write(&temp_path, serde_json::to_string(value)?)?;
+    std::fs::rename(&temp_path, path)
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or_default()
+}
+
+impl History {
+    pub fn load(app: &AppHandle) -> Self {
+        let path = data_path(app, "history.json");
+        let menus = load_file(&path);
+
+        History { path, menus }
+    }
+
+    pub fn record(&mut self, menu: &str, full_string: &str) {
+        let now = now();
+        let entries = self.menus.entry(menu.to_string()).or_default();
+        let usage = entries.entry(full_string.to_string()).or_default();
+        usage.count += 1;
+        usage.last_used = now;
+
+        while entries.len() > MAX_HISTORY_ENTRIES {
+            let Some(least_used) = entries
+                .iter()
+                .filter(|(other, _)| *other != full_string)
+                .min_by_key(|(_, usage)| (usage.frecency(now), usage.last_used))
+                .map(|(other, _)| other.clone())
+            else {
+                break;
+            };
+            entries.remove(&least_used);
+        }
+
+        if let Err(e) = save_file(&self.path, &self.menus) {
+            eprintln!("Could not save history: {}", e);
+        }
+    }
+
+    pub fn frecency(&self, menu: &str, full_string: &str) -> u64 {
+        let now = now();
+
+        self.menus
+            .get(menu)
+            .and_then(|entries| entries.get(full_string))
+            .map_or(0, |usage| usage.frecency(now))
+    }
+}
+
+/// The selection letters entries were given, by menu, then by the entry's
+/// full string, as a byte index for each filter step.
+pub struct Assignments {
+    path: PathBuf,
+    menus: HashMap<String, HashMap<String, Vec<usize>>>,
+    changed: bool,
+}
+
+impl Assignments {
+    pub fn load(app: &AppHandle) -> Self {
+        let path = data_path(app, "assignments.json");
+        let menus = load_file(&path);
+
+        Assignments {
+            path,
+            menus,
+            changed: false,
+        }
+    }
+
+    pub fn get(&self, menu: &str, full_string: &str, step: usize) -> Option<usize> {
+        self.menus
+            .get(menu)
+            .and_then(|entries| entries.get(full_string))
+            .and_then(|indices| indices.get(step).copied())
+    }
+
+    /// Remembers the selections made at a filter step, until they are saved.
+    pub fn set<'a>(
+        &mut self,
+        menu: &str,
+        step: usize,
+        selections: impl Iterator<Item = (&'a str, usize)>,
+    ) {
+        let selections: Vec<(&str, usize)> = selections.collect();
+        let entries = self.menus.entry(menu.to_string()).or_default();
+
+        for &(full_string, index) in &selections {
+            let indices = entries.entry(full_string.to_string()).or_default();
+            if indices.get(step) == Some(&index) {
+                continue;
+            }
+
+            indices.truncate(step);
+            indices.resize(step, usize::MAX);
+            indices.push(index);
+            self.changed = true;
+        }
+
+        // Every listed entry is selected at the first step, so the others are
+        // gone from the menu
+        if step == 0 && entries.len() > MAX_ASSIGNED_ENTRIES {
+            let listed: HashSet<&str> = selections.iter().map(|(s, _)| *s).collect();
+            entries.retain(|full_string, _| listed.contains(full_string.as_str()));
+            self.changed = true;
+        }
+    }
+
+    /// Saves the assignments if any changed since they were last saved.
+    pub fn save(&mut self) {
+        if !self.changed {
+            return;
+        }
+
+        match save_file(&self.path, &self.menus) {
+            Ok(()) => self.changed = false,
+            Err(e) => eprintln!("Could not save selection assignments: {}", e),
+        }
+    }
+}
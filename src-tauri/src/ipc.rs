@@ -0,0 +1,180 @@
+use serde::Deserialize;
+use serde_json::{json, Value};
+use std::{
+    io::{BufRead, BufReader, ErrorKind, Write},
+    os::unix::{
+        fs::{DirBuilderExt, MetadataExt, PermissionsExt},
+        net::{UnixListener, UnixStream},
+    },
+    path::PathBuf,
+    sync::Mutex,
+};
+use tauri::{AppHandle, Manager};
+
+use crate::{
+    config,
+    menu::{self, Activation, Menu},
+};
+
+/// A request sent to the control socket, one JSON object per line.
+#[derive(Deserialize)]
+#[serde(tag = "op", rename_all = "snake_case")]
+enum Request {
+    Open {
+        name: String,
+        #[serde(default)]
+        entries: Option<Vec<String>>,
+    },
+    Close,
+    Reload,
+    ListMenus,
+    LastActivated,
+}
+
+fn handle(app: &AppHandle, request: Request) -> Result<Value, String> {
+    match request {
+        Request::Open { name, entries } => {
+            // Checked before opening, since running them needs the menu's command
+            if entries.is_some()
+                && app
+                    .state::<Mutex<Vec<Menu>>>()
+                    .lock()
+                    .unwrap()
+                    .iter()
+                    .find(|menu| menu.name.as_ref() == Some(&name))
+                    .is_some_and(|menu| !menu.runs_plain_entries())
+            {
+                return Err(format!("Menu {} has no command for injected entries", name));
+            }
+            if crate::open_matching(app, |menu| menu.name.as_ref() == Some(&name), entries) {
+                Ok(Value::Null)
+            } else {
+                Err(format!("No menu is named {}", name))
+            }
+        }
+        Request::Close => {
+            menu::close(app.clone());
+            Ok(Value::Null)
+        }
+        Request::Reload => config::reload(app).map(|()| Value::Null),
+        Request::ListMenus => Ok(app
+            .state::<Mutex<Vec<Menu>>>()
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|menu| {
+                json!({
+                    "name": menu.name,
+                    "hotkey": menu.shortcut.map(|shortcut| shortcut.into_string()),
+                })
+            })
+            .collect()),
+        Request::LastActivated => {
+            serde_json::to_value(&*app.state::<Mutex<Option<Activation>>>().lock().unwrap())
+                .map_err(|e| e.to_string())
+        }
+    }
+}
+
+fn handle_connection(app: &AppHandle, stream: UnixStream) {
+    let mut writer = match stream.try_clone() {
+        Ok(writer) => writer,
+        Err(e) => {
+            eprintln!("Could not clone control connection: {}", e);
+            return;
+        }
+    };
+
+    for line in BufReader::new(stream).lines().map_while(Result::ok) {
+        if line.trim().is_empty() {
+            continue;
+        }
+
+        let response = match serde_json::from_str::<Request>(&line) {
+            Ok(request) => match handle(app, request) {
+                Ok(data) => json!({ "ok": true, "data": data }),
+                Err(e) => json!({ "ok": false, "error": e }),
+            },
+            Err(e) => json!({ "ok": false, "error": format!("Invalid request: {}", e) }),
+        };
+
+        if writeln!(writer, "{}", response).is_err() {
+            break;
+        }
+    }
+}
+
+/// The directory the socket is made in, which only the current user can
+/// access. The runtime directory already is one, otherwise one is made in the
+/// temporary directory, which other users may share.
+fn socket_dir(app: &AppHandle) -> std::io::Result<PathBuf> {
+    if let Ok(dir) = app.path().runtime_dir() {
+        return Ok(dir);
+    }
+
+    // SAFETY: getuid has no preconditions and can't fail
+    let uid = unsafe { libc::getuid() };
+    let dir = std::env::temp_dir().join(format!("quick-find-{}", uid));
+
+    match std::fs::DirBuilder::new().mode(0o700).create(&dir) {
+        Err(e) if e.kind() != ErrorKind::AlreadyExists => return Err(e),
+        _ => {}
+    }
+
+    // Another user may have made it first
+    let metadata = std::fs::symlink_metadata(&dir)?;
+    if !metadata.is_dir() || metadata.uid() != uid || metadata.mode() & 0o077 != 0 {
+        return Err(std::io::Error::new(
+            ErrorKind::PermissionDenied,
+            format!("{} is not private to the current user", dir.display()),
+        ));
+    }
+
+    Ok(dir)
+}
+
+pub fn start_listening(app_handle: &AppHandle) {
+    let app = app_handle.clone();
+
+    let socket_path = match socket_dir(&app) {
+        Ok(dir) => dir.join("quick-find.sock"),
+        Err(e) => {
+            eprintln!("Could not make control socket directory: {}", e);
+            return;
+        }
+    };
+
+    // A previous instance may have left its socket behind
+    if socket_path.exists() {
+        if let Err(e) = std::fs::remove_file(&socket_path) {
+            eprintln!("Could not remove stale control socket: {}", e);
+            return;
+        }
+    }
+
+    let listener = match UnixListener::bind(&socket_path) {
+        Ok(listener) => listener,
+        Err(e) => {
+            eprintln!("Could not bind control socket: {}", e);
+            return;
+        }
+    };
+    let permissions = std::fs::Permissions::from_mode(0o600);
+    if let Err(e) = std::fs::set_permissions(&socket_path, permissions) {
+        eprintln!("Could not restrict control socket permissions: {}", e);
+        return;
+    }
+    eprintln!("Control socket listening at {}", socket_path.display());
+
+    std::thread::spawn(move || {
+        for stream in listener.incoming() {
+            match stream {
+                Ok(stream) => {
+                    let app = app.clone();
+                    std::thread::spawn(move || handle_connection(&app, stream));
+                }
+                Err(e) => eprintln!("Control connection error: {:?}", e),
+            }
+        }
+    });
+}
@@ -0,0 +1,135 @@
+use schemars::JsonSchema;
+use serde::Deserialize;
+use std::collections::HashMap;
+
+/// Physical key codes, as sent by the frontend, in the same rows and
+/// columns as a layout's rows.
+#[rustfmt::skip]
+const CODES: [[&str; 10]; 4] = [
+    ["Digit1", "Digit2", "Digit3", "Digit4", "Digit5", "Digit6", "Digit7", "Digit8", "Digit9", "Digit0"],
+    ["KeyQ", "KeyW", "KeyE", "KeyR", "KeyT", "KeyY", "KeyU", "KeyI", "KeyO", "KeyP"],
+    ["KeyA", "KeyS", "KeyD", "KeyF", "KeyG", "KeyH", "KeyJ", "KeyK", "KeyL", "Semicolon"],
+    ["KeyZ", "KeyX", "KeyC", "KeyV", "KeyB", "KeyN", "KeyM", "Comma", "Period", "Slash"],
+];
+
+/// How hard each row is to reach, from the number row down.
+const ROW_EFFORT: [usize; 4] = [4, 1, 0, 2];
+
+/// Effort for characters that aren't on the layout.
+const MISSING_EFFORT: usize = 5;
+
+#[derive(Debug, Deserialize, JsonSchema, Clone, Copy)]
+#[serde(rename_all = "lowercase")]
+pub enum NamedLayout {
+    Qwerty,
+    Dvorak,
+    Colemak,
+}
+
+/// A keyboard layout, either named or given as the characters of the number,
+/// top, home and bottom rows.
+#[derive(Debug, Deserialize, JsonSchema, Clone)]
+#[serde(untagged)]
+pub enum KeyboardLayout {
+    Named(NamedLayout),
+    /// The characters of the number, top, home and bottom rows, from left
+    /// to right.
+    Custom(Vec<String>),
+}
+
+impl KeyboardLayout {
+    fn rows(&self) -> Vec<Vec<char>> {
+        let rows: Vec<&str> = match self {
+            KeyboardLayout::Named(NamedLayout::Qwerty) => {
+                vec!["1234567890", "qwertyuiop", "asdfghjkl;", "zxcvbnm,./"]
+            }
+            KeyboardLayout::Named(NamedLayout::Dvorak) => {
+                vec!["1234567890", "',.pyfgcrl", "aoeuidhtns", ";qjkxbmwvz"]
+            }
+            KeyboardLayout::Named(NamedLayout::Colemak) => {
+                vec!["1234567890", "qwfpgjluy;", "arstdhneio", "zxcvbkm,./"]
+            }
+            KeyboardLayout::Custom(rows) => rows.iter().map(String::as_str).collect(),
+        };
+
+        rows.iter()
+            .map(|row| row.chars().flat_map(char::to_lowercase).collect())
+            .collect()
+    }
+
+    /// The effort of typing each character, scaled by `weight`. The home row
+    /// is easiest, and the two middle columns need a stretch.
+    pub fn key_costs(&self, weight: usize) -> HashMap<char, usize> {
+        let mut costs = HashMap::new();
+
+        for (row, chars) in self.rows().iter().enumerate().take(ROW_EFFORT.len()) {
+            for (column, c) in chars.iter().enumerate() {
+                let stretch = if column == 4 || column == 5 { 1 } else { 0 };
+                costs
+                    .entry(*c)
+                    .or_insert((ROW_EFFORT[row] + stretch).saturating_mul(weight));
+            }
+        }
+
+        costs
+    }
+
+    /// The character this layout puts on a physical key.
+    pub fn char_for_code(&self, code: &str, shift: bool) -> Option<char> {
+        let rows = self.rows();
+
+        CODES.iter().enumerate().find_map(|(row, codes)| {
+            let column = codes.iter().position(|c| *c == code)?;
+            let c = *rows.get(row)?.get(column)?;
+
+            Some(if shift {
+                c.to_uppercase().next().unwrap_or(c)
+            } else {
+                c
+            })
+        })
+    }
+}
+
+/// The cost of characters that aren't on the layout, which are treated as
+/// the hardest to reach.
+pub fn missing_key_cost(weight: usize) -> usize {
+    MISSING_EFFORT.saturating_mul(weight)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn char_for_code_follows_the_layout() {
+        let qwerty = KeyboardLayout::Named(NamedLayout::Qwerty);
+        let colemak = KeyboardLayout::Named(NamedLayout::Colemak);
+
+        assert_eq!(qwerty.char_for_code("KeyS", false), Some('s'));
+        assert_eq!(colemak.char_for_code("KeyS", false), Some('r'));
+        assert_eq!(colemak.char_for_code("KeyS", true), Some('R'));
+        assert_eq!(colemak.char_for_code("Enter", false), None);
+    }
+
+    #[test]
+    fn char_for_code_reads_custom_rows() {
+        let short = KeyboardLayout::Custom(vec!["1234567890".to_string(), "ab".to_string()]);
+
+        assert_eq!(short.char_for_code("KeyW", false), Some('b'));
+        assert_eq!(short.char_for_code("KeyE", false), None);
+        assert_eq!(short.char_for_code("KeyA", false), None);
+    }
+
+    #[test]
+    fn home_row_costs_less_than_top_row() {
+        let costs = KeyboardLayout::Named(NamedLayout::Qwerty).key_costs(3);
+
+        assert!(costs[&'f'] < costs[&'r']);
+        assert!(costs[&'r'] < costs[&'v']);
+        assert!(costs[&'v'] < costs[&'4']);
+        assert!(costs[&'f'] < costs[&'g']);
+        assert_eq!(costs[&'f'], 0);
+        assert!(costs[&'4'] < missing_key_cost(3));
+    }
+}
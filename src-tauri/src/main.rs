@@ -2,11 +2,18 @@
 #![cfg_attr(not(debug_assertions), windows_subsystem = "windows")]
 
 mod config;
+mod diagnostics;
 mod entry;
+mod history;
+#[cfg(unix)]
+mod ipc;
+mod layout;
 mod menu;
 
-use menu::Menu;
-use std::sync::Mutex;
+use entry::{ActionType, Entry};
+use history::{Assignments, History};
+use menu::{Activation, Menu};
+use std::{io::BufRead, sync::Mutex};
 use tauri::{
     menu::{Menu as ContextMenu, MenuItem},
     tray::TrayIconBuilder,
@@ -46,22 +53,56 @@ fn open_window(app: &AppHandle) -> (tauri::Window, tauri::Webview) {
     (window, webview)
 }
 
-fn open(app: &AppHandle, menu: &mut Menu) {
-    println!("Opened");
+fn open(app: &AppHandle, menu: &mut Menu, injected_entries: Option<Vec<String>>) {
+    eprintln!("Opened");
 
     let (window, webview) = open_window(app);
 
     window.set_focus().expect("Could not focus window");
     webview.set_focus().expect("Could not focus webview");
     menu.emit_css(app);
+    menu.reset_navigation();
+    menu.set_injected_entries(injected_entries);
     menu.get_entries(app);
     window
         .emit("opened", &menu.current_entries)
         .expect("Could not emit initial entries");
 }
 
+fn open_matching(
+    app: &AppHandle,
+    matches: impl Fn(&Menu) -> bool,
+    injected_entries: Option<Vec<String>>,
+) -> bool {
+    for (index, menu) in app
+        .state::<Mutex<Vec<Menu>>>()
+        .lock()
+        .unwrap()
+        .iter_mut()
+        .enumerate()
+    {
+        if matches(menu) {
+            open(app, menu, injected_entries);
+            *app.state::<Mutex<usize>>().lock().unwrap() = index;
+            return true;
+        }
+    }
+
+    false
+}
+
+/// Handles the arguments of a second launch, forwarded by the single
+/// instance plugin.
+fn handle_args(app: &AppHandle, args: &[String]) {
+    if let [_, command, name, ..] = args {
+        if command == "open" && !open_matching(app, |menu| menu.name.as_ref() == Some(name), None) {
+            error(app, format!("No menu is named {}", name));
+        }
+    }
+}
+
 fn error(app: &AppHandle, message: String) {
-    println!("Error: {}", message);
+    eprintln!("Error: {}", message);
 
     let (window, _) = open_window(app);
 
@@ -72,11 +113,64 @@ fn error(app: &AppHandle, message: String) {
     });
 }
 
+/// Marks the app as running with `--dmenu`, selecting a line from stdin
+/// instead of listening for hotkeys.
+pub struct DmenuMode;
+
+fn read_dmenu_entries() -> Vec<Entry> {
+    std::io::stdin()
+        .lock()
+        .lines()
+        .map_while(Result::ok)
+        .filter(|line| !line.is_empty())
+        .map(|line| Entry::new(line.clone(), line, ActionType::Print))
+        .collect()
+}
+
 fn main() {
-    tauri::Builder::default()
+    if std::env::args().nth(1).as_deref() == Some("schema") {
+        println!("{}", config::schema());
+        return;
+    }
+
+    let dmenu_entries = std::env::args()
+        .skip(1)
+        .any(|arg| arg == "--dmenu")
+        .then(read_dmenu_entries);
+
+    let mut builder = tauri::Builder::default();
+    if dmenu_entries.is_none() {
+        builder = builder.plugin(tauri_plugin_single_instance::init(|app, args, _cwd| {
+            handle_args(app, &args);
+        }));
+    }
+
+    builder
         .plugin(tauri_plugin_autostart::Builder::new().build())
         .plugin(tauri_plugin_opener::init())
-        .setup(|app| {
+        .plugin(tauri_plugin_clipboard_manager::init())
+        .setup(move |app| {
+            if let Some(entries) = dmenu_entries {
+                let menu = config::dmenu_menu(app.handle(), entries);
+                app.manage(Mutex::new(vec![menu]));
+                app.manage(Mutex::new(0usize));
+                app.manage(Mutex::new(None::<Activation>));
+                app.manage(DmenuMode);
+
+                let app = app.handle().clone();
+                // Workaround for events emitted as the app opens not being received
+                tauri::async_runtime::spawn(async move {
+                    std::thread::sleep(std::time::Duration::from_millis(200));
+                    open(
+                        &app,
+                        &mut app.state::<Mutex<Vec<Menu>>>().lock().unwrap()[0],
+                        None,
+                    );
+                });
+
+                return Ok(());
+            }
+
             #[cfg(not(debug_assertions))]
             {
                 app.handle().plugin(tauri_plugin_autostart::init(
@@ -96,24 +190,19 @@ fn main() {
 
             app.manage(Mutex::new(Vec::<Menu>::new()));
             app.manage(Mutex::new(usize::MAX));
+            app.manage(Mutex::new(None::<Activation>));
+            app.manage(Mutex::new(History::load(app.handle())));
+            app.manage(Mutex::new(Assignments::load(app.handle())));
 
             app.handle().plugin(
                 tauri_plugin_global_shortcut::Builder::new()
                     .with_handler(|app, shortcut, event| {
                         if event.state == ShortcutState::Pressed {
-                            for (index, menu) in app
-                                .state::<Mutex<Vec<Menu>>>()
-                                .lock()
-                                .unwrap()
-                                .iter_mut()
-                                .enumerate()
-                            {
-                                if &menu.shortcut == shortcut {
-                                    open(app, menu);
-                                    *app.state::<Mutex<usize>>().lock().unwrap() = index;
-                                    break;
-                                }
-                            }
+                            open_matching(
+                                app,
+                                |menu| menu.shortcut.as_ref() == Some(shortcut),
+                                None,
+                            );
                         }
                     })
                     .build(),
@@ -121,12 +210,16 @@ fn main() {
 
             config::ensure_exists(app.handle());
             config::start_listening(app.handle());
+            #[cfg(unix)]
+            ipc::start_listening(app.handle());
 
             Ok(())
         })
         .invoke_handler(tauri::generate_handler![
             menu::close,
             menu::filter_entries,
+            menu::navigate_up,
+            menu::undo_filter,
             config::open_config,
         ])
         .run(tauri::generate_context!())
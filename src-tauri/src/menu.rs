@@ -1,15 +1,55 @@
 use regex::Regex;
-use std::{fs::read_dir, path::Path, sync::Mutex};
+use std::{
+    cmp::Reverse,
+    collections::HashMap,
+    fs::read_dir,
+    io::Read,
+    path::Path,
+    process::Stdio,
+    sync::{mpsc, Mutex},
+    time::{Duration, Instant},
+};
 use tauri::{AppHandle, Emitter, Manager, State};
 use tauri_plugin_global_shortcut::Shortcut;
 
 use crate::{
-    config::Action,
-    entry::{ActionType, Entry},
+    config::{Action, CommandTemplate, Global, Modifier, SelectionMode, SelectionStrategy},
+    entry::{match_key, shell_command, ActionType, Entry, Scoring},
+    history::{Assignments, History},
+    layout::{missing_key_cost, KeyboardLayout},
 };
 
+/// Modifier keys held with a letter, sent by the frontend.
+#[derive(serde::Deserialize, Default, Clone, Copy)]
+pub struct Modifiers {
+    ctrl: bool,
+    alt: bool,
+    shift: bool,
+}
+
+impl Modifiers {
+    fn pressed(self) -> impl Iterator<Item = Modifier> {
+        [
+            (self.ctrl, Modifier::Ctrl),
+            (self.alt, Modifier::Alt),
+            (self.shift, Modifier::Shift),
+        ]
+        .into_iter()
+        .filter_map(|(pressed, modifier)| pressed.then_some(modifier))
+    }
+}
+
+/// The most recently activated entry, kept for the control socket.
+#[derive(serde::Serialize, Clone)]
+pub struct Activation {
+    pub menu: Option<String>,
+    pub string: String,
+    pub full_string: String,
+}
+
 pub struct Menu {
-    pub shortcut: Shortcut,
+    pub shortcut: Option<Shortcut>,
+    pub name: Option<String>,
     pub current_entries: Vec<Entry>,
     entries: Vec<Entry>,
     action: Action,
@@ -18,94 +58,278 @@ pub struct Menu {
     match_allowed_chars_case: bool,
     allowed_regex: Option<Regex>,
     match_selection_case: bool,
+    fold_diacritics: bool,
     minimize_keys: bool,
     remove_extension: bool,
-    command: Option<String>,
+    command: Option<CommandTemplate>,
+    modifier_actions: HashMap<Modifier, ActionType>,
     custom_css: Option<String>,
     ignored_files: Vec<String>,
+    navigate_directories: bool,
+    quote_placeholders: bool,
+    frecency: bool,
+    stable_selections: bool,
+    selection_mode: SelectionMode,
+    selection_strategy: SelectionStrategy,
+    scoring: Scoring,
+    keyboard_layout: Option<KeyboardLayout>,
+    recursive: bool,
+    max_depth: Option<usize>,
+    entries_command: Option<String>,
+    entries_command_timeout: Duration,
+    entries_command_cache_ttl: Option<Duration>,
+    entries_command_cache: Option<(Instant, Vec<Entry>)>,
+    directory_stack: Vec<String>,
+    injected_entries: Option<Vec<Entry>>,
+    filter_history: Vec<Vec<Entry>>,
+}
+
+/// Where a menu's entries come from: those listed in the config, the files
+/// in a directory and the output of a command.
+#[derive(Default)]
+pub struct EntrySource {
+    pub entries: Vec<Entry>,
+    pub directory: Option<String>,
+    pub recursive: bool,
+    pub max_depth: Option<usize>,
+    pub entries_command: Option<String>,
+    pub entries_command_timeout: Duration,
+    pub entries_command_cache_ttl: Option<Duration>,
 }
 
 impl Menu {
+    /// Builds a menu from its `settings`, with any global overrides already
+    /// applied. `allowed_regex` is `settings.allowed_regex`, compiled.
     pub fn new(
-        shortcut: Shortcut,
-        entries: Vec<Entry>,
+        shortcut: Option<Shortcut>,
+        name: Option<String>,
         action: Action,
-        directory: Option<String>,
-        allowed_chars: String,
-        match_allowed_chars_case: bool,
+        command: Option<CommandTemplate>,
+        modifier_actions: HashMap<Modifier, ActionType>,
         allowed_regex: Option<Regex>,
-        match_selection_case: bool,
-        minimize_keys: bool,
-        remove_extension: bool,
-        command: Option<String>,
-        custom_css: Option<String>,
-        ignored_files: Vec<String>,
+        settings: &Global,
+        source: EntrySource,
     ) -> Self {
         Menu {
             shortcut,
-            entries,
+            name,
+            entries: source.entries,
             action,
-            directory,
-            allowed_chars,
-            match_allowed_chars_case,
+            directory: source.directory,
+            allowed_chars: settings.allowed_chars.clone(),
+            match_allowed_chars_case: settings.match_allowed_chars_case,
             allowed_regex,
-            match_selection_case,
-            minimize_keys,
-            remove_extension,
+            match_selection_case: settings.match_selection_case,
+            fold_diacritics: settings.fold_diacritics,
+            minimize_keys: settings.minimize_keys,
+            remove_extension: settings.remove_extension,
             current_entries: Vec::new(),
             command,
-            custom_css,
-            ignored_files,
+            modifier_actions,
+            custom_css: settings.custom_css.clone(),
+            ignored_files: settings.ignored_files.clone(),
+            navigate_directories: settings.navigate_directories,
+            quote_placeholders: settings.quote_placeholders,
+            frecency: settings.frecency,
+            stable_selections: settings.stable_selections,
+            selection_mode: settings.selection_mode,
+            selection_strategy: settings.selection_strategy,
+            scoring: Scoring {
+                prefer_word_starts: settings.prefer_word_starts,
+                key_costs: settings
+                    .keyboard_layout
+                    .as_ref()
+                    .map(|layout| layout.key_costs(settings.key_effort_weight))
+                    .unwrap_or_default(),
+                missing_key_cost: missing_key_cost(settings.key_effort_weight),
+            },
+            keyboard_layout: settings.keyboard_layout.clone(),
+            recursive: source.recursive,
+            max_depth: source.max_depth,
+            entries_command: source.entries_command,
+            entries_command_timeout: source.entries_command_timeout,
+            entries_command_cache_ttl: source.entries_command_cache_ttl,
+            entries_command_cache: None,
+            directory_stack: Vec::new(),
+            injected_entries: None,
+            filter_history: Vec::new(),
         }
     }
 
+    /// Whether entries without a command of their own, such as generated or
+    /// injected ones, can be run.
+    pub fn runs_plain_entries(&self) -> bool {
+        matches!(self.action, Action::Open) || self.command.is_some()
+    }
+
+    /// Replaces the entries listed at the top level until the next call.
+    pub fn set_injected_entries(&mut self, values: Option<Vec<String>>) {
+        self.injected_entries = values.map(|values| {
+            values
+                .into_iter()
+                .map(|value| Entry::new(value.clone(), value, self.action_type()))
+                .collect()
+        });
+    }
+
+    pub fn reset_navigation(&mut self) {
+        self.directory_stack.clear();
+    }
+
+    fn current_directory(&self) -> Option<&String> {
+        self.directory_stack.last().or(self.directory.as_ref())
+    }
+
     pub fn get_entries(&mut self, app: &AppHandle) {
-        self.current_entries = match &self.directory {
-            Some(dir) => match read_dir(&dir) {
-                Ok(dir) => dir
-                    .filter_map(|res| res.ok())
-                    .filter_map(|entry| {
-                        let mut name = entry.file_name().to_string_lossy().into_owned();
-                        let is_dir = entry.file_type().map(|t| t.is_dir()).unwrap_or(false);
-
-                        if is_dir {
-                            name.push('/');
-                        }
+        self.filter_history.clear();
 
-                        if self.ignored_files.contains(&name) {
-                            return None;
-                        }
+        if let (true, Some(entries)) = (self.directory_stack.is_empty(), &self.injected_entries) {
+            self.current_entries = entries.clone();
+        } else {
+            self.current_entries = match self.current_directory() {
+                Some(dir) => {
+                    let mut entries = Vec::new();
 
-                        if !is_dir && self.remove_extension {
-                            name = Path::new(&name)
-                                .file_stem()
-                                .and_then(|s| s.to_str())
-                                .unwrap_or(&name)
-                                .to_string();
+                    match self.read_directory(Path::new(dir), "", 0, &mut entries) {
+                        Ok(()) => entries,
+                        Err(_) => {
+                            crate::error(app, format!("Could not read directory: {}", dir));
+                            Vec::new()
                         }
+                    }
+                }
+                None => Vec::new(),
+            };
+            if self.directory_stack.is_empty() {
+                let command_entries = self.get_command_entries(app);
+                self.current_entries.extend(command_entries);
+                self.current_entries.extend(self.entries.clone());
+            }
+        }
 
-                        let full = entry.path().to_string_lossy().into_owned();
+        if self.frecency {
+            self.sort_by_frecency(app);
+        }
+        self.assign_selections(app);
+    }
 
-                        match &self.action {
-                            Action::Open => Some(Entry::new(name, full, ActionType::Open)),
-                            Action::Command => Some(Entry::new(
-                                name,
-                                full,
-                                ActionType::Command(self.command.clone().unwrap()),
-                            )),
-                        }
-                    })
-                    .collect(),
-                Err(_) => {
-                    crate::error(app, format!("Could not read directory: {}", dir));
-                    Vec::new()
+    pub fn label(&self) -> String {
+        self.name
+            .clone()
+            .or_else(|| self.shortcut.map(|shortcut| shortcut.into_string()))
+            .unwrap_or_default()
+    }
+
+    /// Moves frequently and recently used entries first, so they get the
+    /// first pick of selection letters.
+    fn sort_by_frecency(&mut self, app: &AppHandle) {
+        let Some(history) = app.try_state::<Mutex<History>>() else {
+            return;
+        };
+        let history = history.lock().unwrap();
+        let key = self.label();
+
+        self.current_entries
+            .sort_by_key(|entry| Reverse(history.frecency(&key, &entry.full_string)));
+    }
+
+    fn read_directory(
+        &self,
+        dir: &Path,
+        prefix: &str,
+        depth: usize,
+        entries: &mut Vec<Entry>,
+    ) -> std::io::Result<()> {
+        for entry in read_dir(dir)?.filter_map(|res| res.ok()) {
+            let mut name = entry.file_name().to_string_lossy().into_owned();
+            let is_dir = entry.file_type().map(|t| t.is_dir()).unwrap_or(false);
+
+            if is_dir {
+                name.push('/');
+            }
+
+            if self.ignored_files.contains(&name) {
+                continue;
+            }
+
+            if is_dir && self.recursive && self.max_depth.is_none_or(|max| depth < max) {
+                let sub_prefix = format!("{}{}", prefix, name);
+                if let Err(e) = self.read_directory(&entry.path(), &sub_prefix, depth + 1, entries)
+                {
+                    eprintln!("Could not read subdirectory {}: {}", sub_prefix, e);
                 }
-            },
-            None => Vec::new(),
+                continue;
+            }
+
+            if !is_dir && self.remove_extension {
+                name = Path::new(&name)
+                    .file_stem()
+                    .and_then(|s| s.to_str())
+                    .unwrap_or(&name)
+                    .to_string();
+            }
+
+            let name = format!("{}{}", prefix, name);
+            let full = entry.path().to_string_lossy().into_owned();
+
+            let mut entry = Entry::new(name, full, self.action_type());
+            entry.is_dir = is_dir;
+
+            entries.push(entry);
+        }
+
+        Ok(())
+    }
+
+    fn get_command_entries(&mut self, app: &AppHandle) -> Vec<Entry> {
+        let Some(cmd) = &self.entries_command else {
+            return Vec::new();
         };
-        self.current_entries.extend(self.entries.clone());
 
-        self.find_entry_selections();
+        if let (Some(ttl), Some((time, entries))) =
+            (self.entries_command_cache_ttl, &self.entries_command_cache)
+        {
+            if time.elapsed() < ttl {
+                return entries.clone();
+            }
+        }
+
+        let output = match run_entries_command(cmd, self.entries_command_timeout) {
+            Ok(output) => output,
+            Err(e) => {
+                crate::error(app, format!("Entries command failed: {}", e));
+                return Vec::new();
+            }
+        };
+
+        let entries: Vec<Entry> = output
+            .lines()
+            .map(|line| line.trim_end_matches('\r'))
+            .filter(|line| !line.is_empty())
+            .map(|line| {
+                let (display, value) = line.split_once('\t').unwrap_or((line, line));
+                Entry::new(display.to_string(), value.to_string(), self.action_type())
+            })
+            .collect();
+
+        if self.entries_command_cache_ttl.is_some() {
+            self.entries_command_cache = Some((Instant::now(), entries.clone()));
+        }
+
+        entries
+    }
+
+    fn action_type(&self) -> ActionType {
+        match &self.action {
+            Action::Open => ActionType::Open,
+            Action::Command => ActionType::Command {
+                template: self
+                    .command
+                    .clone()
+                    .expect("Menu with generated entries has no command"),
+                quote: self.quote_placeholders,
+            },
+        }
     }
 
     pub fn emit_css(&self, app: &AppHandle) {
@@ -114,16 +338,283 @@ impl Menu {
         }
     }
 
+    /// Finds selections for the current entries, keeping the ones remembered
+    /// from earlier sessions where they are still valid when enabled.
+    fn assign_selections(&mut self, app: &AppHandle) {
+        if self.selection_mode == SelectionMode::Hint {
+            self.assign_hints();
+            return;
+        }
+
+        let assignments = if self.stable_selections {
+            app.try_state::<Mutex<Assignments>>()
+        } else {
+            None
+        };
+        let key = self.label();
+        let step = self.filter_history.len();
+
+        for entry in &mut self.current_entries {
+            entry.preferred_index = assignments.as_ref().and_then(|assignments| {
+                assignments
+                    .lock()
+                    .unwrap()
+                    .get(&key, &entry.full_string, step)
+            });
+        }
+
+        self.find_entry_selections();
+        self.assign_fallback_labels();
+
+        if let Some(assignments) = assignments {
+            assignments.lock().unwrap().set(
+                &key,
+                step,
+                self.current_entries
+                    .iter()
+                    .filter(|entry| entry.selection_index != usize::MAX)
+                    .map(|entry| (entry.full_string.as_str(), entry.selection_index)),
+            );
+        }
+    }
+
+    /// Gives every entry a label when the menu is listed, then selects the
+    /// next character of each entry's label as it is typed.
+    fn assign_hints(&mut self) {
+        let step = self.filter_history.len();
+
+        if step == 0 {
+            let chars = hint_chars(
+                &self.allowed_chars,
+                self.match_selection_case,
+                self.fold_diacritics,
+            );
+
+            let labels = generate_hints(&chars, self.current_entries.len());
+            for (entry, label) in self.current_entries.iter_mut().zip(labels) {
+                entry.hint_label = Some(label);
+            }
+        }
+
+        for entry in &mut self.current_entries {
+            let label = entry.hint_label.as_deref().unwrap_or_default();
+            entry.selection_letter = label.chars().nth(step).unwrap_or(char::MAX);
+            entry.hint = Some(label.chars().skip(step).collect());
+        }
+    }
+
+    /// Labels the entries that can't be reached by typing their letters,
+    /// because they ran out of selectable characters or can't be told apart,
+    /// so they can be picked from a numbered list instead. If too few digits
+    /// are free of other entries' letters, every entry is labelled.
+    fn assign_fallback_labels(&mut self) {
+        // Entries already being picked from a list keep their labels
+        if self
+            .current_entries
+            .iter()
+            .any(|entry| entry.hint_label.is_some())
+        {
+            for entry in &mut self.current_entries {
+                let label = entry.hint_label.clone().unwrap_or_default();
+                entry.clear_selection();
+                entry.selection_letter = label.chars().next().unwrap_or(char::MAX);
+                entry.hint = Some(label);
+            }
+            return;
+        }
+
+        let mut labelled: Vec<usize> = (0..self.current_entries.len())
+            .filter(|i| self.current_entries[*i].selection_letter == char::MAX)
+            .collect();
+        if labelled.is_empty() {
+            return;
+        }
+
+        let used: Vec<String> = self
+            .current_entries
+            .iter()
+            .filter(|entry| entry.selection_letter != char::MAX)
+            .map(|entry| self.selection_key(entry.selection_letter))
+            .collect();
+        let mut label_chars: String = FALLBACK_LABEL_CHARS
+            .chars()
+            .filter(|c| !used.contains(&self.selection_key(*c)))
+            .collect();
+        if label_chars.chars().count() < 2 {
+            label_chars = FALLBACK_LABEL_CHARS.to_string();
+            labelled = (0..self.current_entries.len()).collect();
+        }
+
+        eprintln!("Picking {} entries from a list", labelled.len());
+
+        let labels = generate_hints(&label_chars, labelled.len());
+        for (i, label) in labelled.into_iter().zip(labels) {
+            let entry = &mut self.current_entries[i];
+            entry.clear_selection();
+            entry.selection_letter = label.chars().next().unwrap_or(char::MAX);
+            entry.hint = Some(label.clone());
+            entry.hint_label = Some(label);
+        }
+    }
+
+    fn selection_key(&self, letter: char) -> String {
+        match_key(letter, self.match_selection_case, self.fold_diacritics)
+    }
+
+    /// Gives as many entries as possible a letter of their own using a
+    /// maximum bipartite matching between entries and letters. Entries left
+    /// over share the letter used by the fewest entries, and are told apart
+    /// by the following keys. This maximizes the entries selected with one
+    /// key, which usually but not always minimizes the total keystrokes.
+    fn find_matched_selections(&mut self) {
+        let mut unproductive_chars: Vec<String> = Vec::new();
+
+        loop {
+            self.clear_selections();
+
+            let candidates: Vec<Vec<(usize, char)>> = self
+                .current_entries
+                .iter()
+                .map(|entry| {
+                    let mut seen = Vec::new();
+                    entry
+                        .get_candidates(
+                            &self.allowed_chars,
+                            &self.allowed_regex,
+                            &unproductive_chars,
+                            self.match_allowed_chars_case,
+                            self.match_selection_case,
+                            self.fold_diacritics,
+                            &self.scoring,
+                        )
+                        .into_iter()
+                        .filter(|(_, c)| {
+                            let key = self.selection_key(*c);
+                            if seen.contains(&key) {
+                                return false;
+                            }
+                            seen.push(key);
+                            true
+                        })
+                        .collect()
+                })
+                .collect();
+            let letters: Vec<Vec<String>> = candidates
+                .iter()
+                .map(|c| c.iter().map(|(_, c)| self.selection_key(*c)).collect())
+                .collect();
+
+            let preferred: Vec<bool> = self
+                .current_entries
+                .iter()
+                .zip(&candidates)
+                .map(|(entry, candidates)| {
+                    entry.preferred_index.is_some()
+                        && candidates.first().map(|(index, _)| *index) == entry.preferred_index
+                })
+                .collect();
+
+            let matching = max_matching(&letters, &preferred);
+            let mut group_sizes: HashMap<&str, usize> = HashMap::new();
+
+            for (i, matched) in matching.iter().enumerate() {
+                if let Some(k) = matched {
+                    self.current_entries[i].select(candidates[i][*k]);
+                    *group_sizes.entry(&letters[i][*k]).or_default() += 1;
+                }
+            }
+
+            for (i, matched) in matching.iter().enumerate() {
+                if matched.is_some() {
+                    continue;
+                }
+
+                let best = (0..candidates[i].len()).min_by_key(|k| {
+                    group_sizes
+                        .get(letters[i][*k].as_str())
+                        .copied()
+                        .unwrap_or(0)
+                });
+                if let Some(k) = best {
+                    self.current_entries[i].select(candidates[i][k]);
+                    *group_sizes.entry(&letters[i][k]).or_default() += 1;
+                }
+            }
+
+            // A letter chosen by every entry doesn't narrow anything down
+            let entry_count = self.current_entries.len();
+            if let (true, Some((&letter, _))) = (
+                entry_count > 1,
+                group_sizes.iter().find(|(_, size)| **size == entry_count),
+            ) {
+                unproductive_chars.push(letter.to_string());
+                eprintln!(
+                    concat!(
+                        "All entries attempted to use the same letter. ",
+                        "Unproductive chars are now \"{}\""
+                    ),
+                    unproductive_chars.concat()
+                );
+                continue;
+            }
+
+            break;
+        }
+    }
+
+    fn clear_selections(&mut self) {
+        for entry in &mut self.current_entries {
+            entry.clear_selection();
+        }
+    }
+
     fn find_entry_selections(&mut self) {
+        if self.current_entries.is_empty() {
+            return;
+        }
+
+        if self.minimize_keys && self.selection_strategy == SelectionStrategy::Matching {
+            self.find_matched_selections();
+            return;
+        }
+
         if self.minimize_keys {
-            let mut unproductive_chars = String::from("");
+            let mut unproductive_chars: Vec<String> = Vec::new();
 
             loop {
-                let mut used_chars = String::from("");
+                self.clear_selections();
+
+                let mut used_chars: Vec<String> = Vec::new();
+                let mut kept = vec![false; self.current_entries.len()];
 
-                for entry in &mut self.current_entries {
+                for (i, entry) in self.current_entries.iter_mut().enumerate() {
                     let disallowed_chars =
-                        [unproductive_chars.as_str(), used_chars.as_str()].concat();
+                        [unproductive_chars.as_slice(), used_chars.as_slice()].concat();
+
+                    if entry.get_preferred_selection(
+                        &self.allowed_chars,
+                        &self.allowed_regex,
+                        &disallowed_chars,
+                        self.match_allowed_chars_case,
+                        self.match_selection_case,
+                        self.fold_diacritics,
+                    ) {
+                        used_chars.push(match_key(
+                            entry.selection_letter,
+                            self.match_selection_case,
+                            self.fold_diacritics,
+                        ));
+                        kept[i] = true;
+                    }
+                }
+
+                for (i, entry) in self.current_entries.iter_mut().enumerate() {
+                    if kept[i] {
+                        continue;
+                    }
+
+                    let disallowed_chars =
+                        [unproductive_chars.as_slice(), used_chars.as_slice()].concat();
 
                     if entry.get_selection(
                         &self.allowed_chars,
@@ -131,15 +622,14 @@ impl Menu {
                         &disallowed_chars,
                         self.match_allowed_chars_case,
                         self.match_selection_case,
+                        self.fold_diacritics,
+                        &self.scoring,
                     ) {
-                        used_chars.push(if self.match_selection_case {
-                            entry.selection_letter
-                        } else {
-                            entry.selection_letter.to_lowercase().next().expect(concat!(
-                                "Could not convert selection letter ",
-                                "to lowercase"
-                            ))
-                        })
+                        used_chars.push(match_key(
+                            entry.selection_letter,
+                            self.match_selection_case,
+                            self.fold_diacritics,
+                        ))
                     } else {
                         entry.get_selection(
                             &self.allowed_chars,
@@ -147,125 +637,580 @@ impl Menu {
                             &unproductive_chars,
                             self.match_allowed_chars_case,
                             self.match_selection_case,
+                            self.fold_diacritics,
+                            &self.scoring,
                         );
                     }
                 }
 
-                let not_same = self
-                    .current_entries
-                    .iter()
-                    .any(|x| x.selection_letter != self.current_entries[0].selection_letter);
-
-                if !not_same {
-                    unproductive_chars.push(self.current_entries[0].selection_letter);
-                    println!(
-                        concat!(
-                            "All entries attempted to use the same letter. ",
-                            "Unproductive chars are now \"{}\""
-                        ),
-                        unproductive_chars
-                    );
-                } else {
+                let first = self.selection_key(self.current_entries[0].selection_letter);
+                let all_same = self.current_entries.len() > 1
+                    && self
+                        .current_entries
+                        .iter()
+                        .all(|x| self.selection_key(x.selection_letter) == first);
+
+                // Entries that ran out of letters are left to the fallback labels
+                if !all_same || self.current_entries[0].selection_letter == char::MAX {
                     break;
-                };
+                }
+
+                unproductive_chars.push(first);
+                eprintln!(
+                    concat!(
+                        "All entries attempted to use the same letter. ",
+                        "Unproductive chars are now \"{}\""
+                    ),
+                    unproductive_chars.concat()
+                );
             }
         } else {
+            self.clear_selections();
+
             for entry in &mut self.current_entries {
+                if entry.get_preferred_selection(
+                    &self.allowed_chars,
+                    &self.allowed_regex,
+                    &[],
+                    self.match_allowed_chars_case,
+                    self.match_selection_case,
+                    self.fold_diacritics,
+                ) {
+                    continue;
+                }
+
                 entry.get_selection(
                     &self.allowed_chars,
                     &self.allowed_regex,
-                    "",
+                    &[],
                     self.match_allowed_chars_case,
                     self.match_selection_case,
+                    self.fold_diacritics,
+                    &self.scoring,
                 );
             }
         }
     }
 
-    fn filter(&mut self, in_letter: char, app: &AppHandle) -> bool {
-        let letter: char;
-        if !self.match_allowed_chars_case {
-            letter = in_letter
-                .to_lowercase()
-                .next()
-                .expect("Could not convert filter letter to lowercase");
-        } else {
-            letter = in_letter
-        }
-
-        let has_match = self.current_entries.iter().any(|x| {
-            if self.match_selection_case {
-                x.selection_letter == letter
-            } else {
-                x.selection_letter
-                    .to_lowercase()
-                    .next()
-                    .expect("Could not convert selection letter to lowercase")
-                    == letter
-            }
-        });
+    fn filter(&mut self, in_letter: char, modifiers: Modifiers, app: &AppHandle) -> bool {
+        let letter = self.selection_key(in_letter);
+
+        let has_match = self
+            .current_entries
+            .iter()
+            .any(|x| self.selection_key(x.selection_letter) == letter);
 
         if !has_match {
             return true;
         };
 
+        self.filter_history.push(self.current_entries.clone());
+        let (match_selection_case, fold_diacritics) =
+            (self.match_selection_case, self.fold_diacritics);
         self.current_entries.retain(|x| {
-            if self.match_selection_case {
-                x.selection_letter == letter
-            } else {
-                x.selection_letter
-                    .to_lowercase()
-                    .next()
-                    .expect("Could not convert selection letter to lowercase")
-                    == letter
-            }
+            match_key(x.selection_letter, match_selection_case, fold_diacritics) == letter
         });
 
         if self.current_entries.len() == 1 {
             let entry = &self.current_entries[0];
-            println!("Activating entry: {}", entry.string);
 
-            self.current_entries[0]
-                .action
-                .activate(app, &entry.full_string);
+            if self.navigate_directories && entry.is_dir {
+                eprintln!("Entering directory: {}", entry.string);
+                self.directory_stack.push(entry.full_string.clone());
+                self.get_entries(app);
+                return true;
+            }
+
+            eprintln!("Activating entry: {}", entry.string);
+            if self.frecency {
+                if let Some(history) = app.try_state::<Mutex<History>>() {
+                    history
+                        .lock()
+                        .unwrap()
+                        .record(&self.label(), &entry.full_string);
+                }
+            }
+            *app.state::<Mutex<Option<Activation>>>().lock().unwrap() = Some(Activation {
+                menu: self.name.clone(),
+                string: entry.string.clone(),
+                full_string: entry.full_string.clone(),
+            });
+
+            let action = modifiers
+                .pressed()
+                .find_map(|modifier| self.modifier_actions.get(&modifier))
+                .unwrap_or(&entry.action);
+            action.activate(app, entry, self.name.as_deref());
 
-            close(app.clone());
+            hide(app);
             return false;
         }
 
-        println!("Filtered to {} entries", self.current_entries.len());
+        eprintln!("Filtered to {} entries", self.current_entries.len());
 
-        for entry in &mut self.current_entries {
-            entry.pos = entry.selection_index + 1;
+        if self.selection_mode == SelectionMode::Letter {
+            for entry in &mut self.current_entries {
+                match &mut entry.hint_label {
+                    Some(label) => *label = label.chars().skip(1).collect(),
+                    None => entry.step_past_selection(),
+                }
+            }
         }
 
-        self.find_entry_selections();
+        self.assign_selections(app);
+
+        true
+    }
+
+    fn undo_filter(&mut self) -> bool {
+        let Some(entries) = self.filter_history.pop() else {
+            return false;
+        };
+
+        eprintln!("Undid filter step, {} entries", entries.len());
+        self.current_entries = entries;
+
+        true
+    }
+
+    fn leave_directory(&mut self, app: &AppHandle) -> bool {
+        if self.directory_stack.pop().is_none() {
+            return false;
+        }
+
+        eprintln!("Returned to parent directory");
+        self.get_entries(app);
 
         true
     }
 }
 
+fn run_entries_command(cmd: &str, timeout: Duration) -> Result<String, String> {
+    let mut child = shell_command(cmd)
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .spawn()
+        .map_err(|e| format!("could not run {}: {}", cmd, e))?;
+
+    let mut stdout = child
+        .stdout
+        .take()
+        .expect("Could not capture command output");
+    let (output_tx, output_rx) = mpsc::channel();
+    std::thread::spawn(move || {
+        let mut output = Vec::new();
+        let _ = output_tx.send(stdout.read_to_end(&mut output).map(|_| output));
+    });
+
+    let deadline = Instant::now() + timeout;
+    let status = loop {
+        match child.try_wait() {
+            Ok(Some(status)) => break status,
+            Ok(None) if Instant::now() >= deadline => {
+                let _ = child.kill();
+                let _ = child.wait();
+                return Err(format!(
+                    "{} timed out after {} ms",
+                    cmd,
+                    timeout.as_millis()
+                ));
+            }
+            Ok(None) => std::thread::sleep(Duration::from_millis(10)),
+            Err(e) => return Err(format!("could not wait for {}: {}", cmd, e)),
+        }
+    };
+
+    if !status.success() {
+        return Err(format!("{} exited with {}", cmd, status));
+    }
+
+    // A process left running in the background can keep the output open after
+    // the shell exits, so reading is bound by the same deadline
+    let output = output_rx
+        .recv_timeout(deadline.saturating_duration_since(Instant::now()))
+        .map_err(|_| {
+            format!(
+                "output of {} was not closed after {} ms",
+                cmd,
+                timeout.as_millis()
+            )
+        })?
+        .map_err(|e| format!("could not read output of {}: {}", cmd, e))?;
+
+    Ok(String::from_utf8_lossy(&output).into_owned())
+}
+
+/// Used for hints when fewer than two allowed characters are set.
+const DEFAULT_HINT_CHARS: &str = "asdfghjkl";
+
+/// The characters hints are made of: the allowed characters, keeping only
+/// the first of those typed the same way, or the defaults if fewer than two
+/// are left.
+fn hint_chars(allowed_chars: &str, match_selection_case: bool, fold_diacritics: bool) -> String {
+    let mut keys = Vec::new();
+    let chars: String = allowed_chars
+        .chars()
+        .filter(|c| {
+            let key = match_key(*c, match_selection_case, fold_diacritics);
+            if keys.contains(&key) {
+                return false;
+            }
+            keys.push(key);
+            true
+        })
+        .collect();
+
+    if chars.chars().count() < 2 {
+        DEFAULT_HINT_CHARS.to_string()
+    } else {
+        chars
+    }
+}
+
+/// Used for entries that can't be reached by typing their letters.
+const FALLBACK_LABEL_CHARS: &str = "1234567890";
+
+/// Generates `count` labels from `chars` that are as short as possible and
+/// where no label is the start of another, shortest first. With fewer than
+/// two distinct characters, at most one label can be made.
+fn generate_hints(chars: &str, count: usize) -> Vec<String> {
+    let mut unique_chars: Vec<char> = Vec::new();
+    for c in chars.chars() {
+        if !unique_chars.contains(&c) {
+            unique_chars.push(c);
+        }
+    }
+
+    let count = match unique_chars.len() {
+        0 => 0,
+        1 => count.min(1),
+        _ => count,
+    };
+    if count == 0 {
+        return Vec::new();
+    }
+
+    let mut hints = vec![String::new()];
+    let mut offset = 0;
+
+    while hints.len() - offset < count || hints.len() == 1 {
+        let prefix = hints[offset].clone();
+        offset += 1;
+        hints.extend(unique_chars.iter().map(|c| format!("{}{}", prefix, c)));
+    }
+
+    hints.into_iter().skip(offset).take(count).collect()
+}
+
+/// Kuhn's algorithm, returning the index into each entry's letters it was
+/// matched with. Entries earlier in the list are matched first. Entries
+/// marked in `preferred` keep their first letter if no earlier one of them
+/// has it, and the rest are matched around them, so remembered letters only
+/// change when they conflict.
+fn max_matching(letters: &[Vec<String>], preferred: &[bool]) -> Vec<Option<usize>> {
+    fn augment<'a>(
+        entry: usize,
+        letters: &'a [Vec<String>],
+        owners: &mut HashMap<&'a str, usize>,
+        matching: &mut [Option<usize>],
+        visited: &mut Vec<&'a str>,
+    ) -> bool {
+        for (k, letter) in letters[entry].iter().enumerate() {
+            if visited.contains(&letter.as_str()) {
+                continue;
+            }
+            visited.push(letter);
+
+            let free = match owners.get(letter.as_str()) {
+                Some(&owner) => augment(owner, letters, owners, matching, visited),
+                None => true,
+            };
+
+            if free {
+                owners.insert(letter, entry);
+                matching[entry] = Some(k);
+                return true;
+            }
+        }
+
+        false
+    }
+
+    let mut owners: HashMap<&str, usize> = HashMap::new();
+    let mut matching = vec![None; letters.len()];
+    let mut pinned: Vec<&str> = Vec::new();
+
+    for (entry, entry_letters) in letters.iter().enumerate() {
+        if !preferred.get(entry).copied().unwrap_or(false) {
+            continue;
+        }
+        if let Some(letter) = entry_letters.first() {
+            if !owners.contains_key(letter.as_str()) {
+                owners.insert(letter.as_str(), entry);
+                matching[entry] = Some(0);
+                pinned.push(letter.as_str());
+            }
+        }
+    }
+
+    for entry in 0..letters.len() {
+        if matching[entry].is_none() {
+            // Pinned letters count as visited, so their entries are never moved
+            augment(
+                entry,
+                letters,
+                &mut owners,
+                &mut matching,
+                &mut pinned.clone(),
+            );
+        }
+    }
+
+    matching
+}
+
 #[tauri::command]
 pub fn filter_entries(
     app: AppHandle,
     state_idx: State<'_, Mutex<usize>>,
     state: State<'_, Mutex<Vec<Menu>>>,
     in_char: char,
+    modifiers: Option<Modifiers>,
+    code: Option<String>,
 ) {
     let idx = *state_idx.lock().expect("Could not lock index mutex");
     let mut state_guard = state.lock().expect("Could not lock state mutex");
     let menu = &mut state_guard[idx];
+    let modifiers = modifiers.unwrap_or_default();
+
+    // Reading the physical key keeps selection working when the system layout changes
+    let in_char = match (&menu.keyboard_layout, code) {
+        (Some(layout), Some(code)) => layout
+            .char_for_code(&code, modifiers.shift)
+            .unwrap_or(in_char),
+        _ => in_char,
+    };
 
-    menu.filter(in_char, &app);
+    menu.filter(in_char, modifiers, &app);
     app.emit("opened", &menu.current_entries)
         .expect("Could not emit filtered entries");
 }
 
+#[tauri::command]
+pub fn undo_filter(
+    app: AppHandle,
+    state_idx: State<'_, Mutex<usize>>,
+    state: State<'_, Mutex<Vec<Menu>>>,
+) {
+    let idx = *state_idx.lock().expect("Could not lock index mutex");
+    let mut state_guard = state.lock().expect("Could not lock state mutex");
+    let Some(menu) = state_guard.get_mut(idx) else {
+        return;
+    };
+
+    // With nothing left to undo, go back to the parent directory instead
+    if menu.undo_filter() || menu.leave_directory(&app) {
+        app.emit("opened", &menu.current_entries)
+            .expect("Could not emit previous entries");
+    }
+}
+
+#[tauri::command]
+pub fn navigate_up(
+    app: AppHandle,
+    state_idx: State<'_, Mutex<usize>>,
+    state: State<'_, Mutex<Vec<Menu>>>,
+) {
+    let idx = *state_idx.lock().expect("Could not lock index mutex");
+    let mut state_guard = state.lock().expect("Could not lock state mutex");
+    let Some(menu) = state_guard.get_mut(idx) else {
+        return;
+    };
+
+    if menu.leave_directory(&app) {
+        app.emit("opened", &menu.current_entries)
+            .expect("Could not emit parent entries");
+    }
+}
+
 #[tauri::command]
 pub fn close(app: AppHandle) {
+    if app.try_state::<crate::DmenuMode>().is_some() {
+        app.exit(1);
+        return;
+    }
+
+    hide(&app);
+}
+
+fn hide(app: &AppHandle) {
     app.get_window("main")
         .expect("Could not get window")
         .hide()
         .expect("Could not hide window");
     *app.state::<Mutex<usize>>().lock().unwrap() = usize::MAX;
+
+    // Saved once per opening rather than at every filter step
+    if let Some(assignments) = app.try_state::<Mutex<Assignments>>() {
+        assignments.lock().unwrap().save();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn letters(entries: &[&[&str]]) -> Vec<Vec<String>> {
+        entries
+            .iter()
+            .map(|letters| letters.iter().map(|letter| letter.to_string()).collect())
+            .collect()
+    }
+
+    fn matched_letters<'a>(letters: &'a [Vec<String>], matching: &[Option<usize>]) -> Vec<&'a str> {
+        matching
+            .iter()
+            .enumerate()
+            .filter_map(|(i, k)| k.map(|k| letters[i][k].as_str()))
+            .collect()
+    }
+
+    fn is_prefix_free(hints: &[String]) -> bool {
+        hints.iter().enumerate().all(|(i, hint)| {
+            hints
+                .iter()
+                .enumerate()
+                .all(|(j, other)| i == j || !other.starts_with(hint.as_str()))
+        })
+    }
+
+    #[test]
+    fn generate_hints_are_prefix_free() {
+        for chars in ["ab", "abc", "asdfghjkl"] {
+            for count in 1..=200 {
+                let hints = generate_hints(chars, count);
+
+                assert_eq!(hints.len(), count, "{} labels from {}", count, chars);
+                assert!(is_prefix_free(&hints), "{} labels from {}", count, chars);
+            }
+        }
+    }
+
+    #[test]
+    fn generate_hints_are_as_short_as_possible() {
+        for chars in ["ab", "abc", "asdfghjkl"] {
+            let size = chars.len();
+
+            for count in 1..=200 {
+                let longest = generate_hints(chars, count)
+                    .iter()
+                    .map(String::len)
+                    .max()
+                    .unwrap();
+                let mut shortest_possible = 1;
+                while size.pow(shortest_possible) < count {
+                    shortest_possible += 1;
+                }
+
+                assert_eq!(longest, shortest_possible as usize);
+            }
+        }
+    }
+
+    #[test]
+    fn generate_hints_puts_shortest_first() {
+        assert_eq!(generate_hints("abc", 3), vec!["a", "b", "c"]);
+        assert_eq!(generate_hints("abc", 4), vec!["b", "c", "aa", "ab"]);
+    }
+
+    #[test]
+    fn generate_hints_with_small_alphabets() {
+        assert!(generate_hints("", 3).is_empty());
+        assert!(generate_hints("abc", 0).is_empty());
+        assert_eq!(generate_hints("a", 3), vec!["a"]);
+        assert_eq!(generate_hints("aaa", 2), vec!["a"]);
+        assert_eq!(generate_hints("abab", 2), vec!["a", "b"]);
+    }
+
+    #[test]
+    fn hint_chars_are_typed_differently() {
+        assert_eq!(hint_chars("asdfASDF", false, false), "asdf");
+        assert_eq!(hint_chars("asdfASDF", true, false), "asdfASDF");
+        assert_eq!(hint_chars("eéx", false, true), "ex");
+        assert_eq!(hint_chars("eéx", false, false), "eéx");
+    }
+
+    #[test]
+    fn hint_chars_fall_back_to_the_defaults() {
+        assert_eq!(hint_chars("", false, false), DEFAULT_HINT_CHARS);
+        assert_eq!(hint_chars("aA", false, false), DEFAULT_HINT_CHARS);
+        assert_eq!(hint_chars("eé", false, true), DEFAULT_HINT_CHARS);
+    }
+
+    #[test]
+    fn max_matching_of_nothing_is_empty() {
+        assert!(max_matching(&[], &[]).is_empty());
+    }
+
+    #[test]
+    fn max_matching_moves_earlier_entries_aside() {
+        let letters = letters(&[&["a", "b"], &["a"]]);
+
+        assert_eq!(max_matching(&letters, &[]), vec![Some(1), Some(0)]);
+    }
+
+    #[test]
+    fn max_matching_follows_long_augmenting_paths() {
+        let letters = letters(&[&["a", "b"], &["b", "c"], &["a"]]);
+        let matching = max_matching(&letters, &[]);
+
+        assert!(matching.iter().all(Option::is_some));
+        assert_eq!(matching[2], Some(0));
+    }
+
+    #[test]
+    fn max_matching_leaves_out_entries_that_cant_be_matched() {
+        let letters = letters(&[&["a"], &["a"], &["b"], &[]]);
+        let matching = max_matching(&letters, &[]);
+        let mut matched = matched_letters(&letters, &matching);
+
+        assert_eq!(matched.len(), 2);
+        assert_eq!(matching[3], None);
+        matched.sort();
+        assert_eq!(matched, vec!["a", "b"]);
+    }
+
+    #[test]
+    fn max_matching_keeps_preferred_letters() {
+        // A remembers x, so B takes its free y instead of moving A to z
+        let letters = letters(&[&["x", "z"], &["x", "y"]]);
+
+        assert_eq!(
+            max_matching(&letters, &[true, false]),
+            vec![Some(0), Some(1)]
+        );
+    }
+
+    #[test]
+    fn max_matching_resolves_conflicting_preferences() {
+        let letters = letters(&[&["x", "z"], &["x"]]);
+
+        assert_eq!(
+            max_matching(&letters, &[false, true]),
+            vec![Some(1), Some(0)]
+        );
+        assert_eq!(max_matching(&letters, &[true, true]), vec![Some(0), None]);
+    }
+
+    #[test]
+    fn max_matching_uses_each_letter_once() {
+        let letters = letters(&[&["a", "b", "c"], &["a", "b"], &["a"], &["c", "a"]]);
+        let matching = max_matching(&letters, &[]);
+        let mut matched = matched_letters(&letters, &matching);
+
+        assert_eq!(matched.len(), 3);
+        matched.sort();
+        matched.dedup();
+        assert_eq!(matched.len(), 3);
+    }
 }
@@ -7,9 +7,20 @@ const entryClipText = "...";
 const contentSize = entrySize - 2 * entryClipText.length;
 const halfSize = (contentSize - 1) / 2;
 
+const segmenter = new Intl.Segmenter(undefined, { granularity: "grapheme" });
+
+// Splits text into what is displayed as single characters, so clipping never
+// separates combining marks or emoji sequences
+function graphemes(text: string): string[] {
+    return Array.from(segmenter.segment(text), (segment) => segment.segment);
+}
+
 type Entry = {
     string: string,
-    selection_index: number,
+    pre: string,
+    current: string,
+    post: string,
+    hint?: string,
 }
 
 listen('opened', (event) => {
@@ -25,45 +36,66 @@ listen('opened', (event) => {
         let entryDiv = document.createElement("div");
         entryDiv.className = "entry";
 
-        if (entry.selection_index > 0) {
+        if (entry.hint !== undefined) {
+            let hint = document.createElement("em");
+            hint.className = "hint";
+            hint.innerText = entry.hint;
+            entryDiv.appendChild(hint);
+
+            let chars = graphemes(entry.string);
+            let text = document.createElement("span");
+            text.className = "post";
+            text.innerText = chars.length > entrySize
+                ? chars.slice(0, entrySize - entryClipText.length).join("") + entryClipText
+                : entry.string;
+            entryDiv.appendChild(text);
+
+            entriesElement.appendChild(entryDiv);
+            continue;
+        }
+
+        let pre = graphemes(entry.pre);
+        let post = graphemes(entry.post);
+        let length = pre.length + 1 + post.length;
+
+        if (pre.length > 0) {
             let preText = document.createElement("span");
             preText.className = "pre";
 
             let start = Math.max(0, Math.min(
-                entry.selection_index - halfSize, 
-                entry.string.length - entrySize + entryClipText.length
+                pre.length - halfSize, 
+                length - entrySize + entryClipText.length
             ));
             if (start > 0 && start <= entryClipText.length) {
                 start = 0;
             }
             preText.innerText = (start > 0 ? entryClipText : "")
-                + entry.string.slice(start, entry.selection_index);
+                + pre.slice(start).join("");
             entryDiv.appendChild(preText);
         }
         
         let entryLetter = document.createElement("em");
         entryLetter.className = "current";
 
-        entryLetter.innerText = entry.string[entry.selection_index];
+        entryLetter.innerText = entry.current;
         entryDiv.appendChild(entryLetter);
         
-        if (entry.string.length >= entry.selection_index) {
+        if (post.length > 0) {
             let postText = document.createElement("span");
             postText.className = "post";
 
-            let end = Math.min(entry.string.length, Math.max(
-                entry.selection_index + halfSize + 1,
-                entrySize - entryClipText.length
+            let end = Math.min(post.length, Math.max(
+                halfSize,
+                entrySize - entryClipText.length - pre.length - 1
             ));
             if (
-                end < entry.string.length 
-                && end >= entry.string.length - entryClipText.length
+                end < post.length 
+                && end >= post.length - entryClipText.length
             ) {
-                end = entry.string.length;
+                end = post.length;
             }
-            postText.innerText = entry.string
-                .slice(entry.selection_index + 1, end)
-                + (end < entry.string.length ? entryClipText : "");
+            postText.innerText = post.slice(0, end).join("")
+                + (end < post.length ? entryClipText : "");
             entryDiv.appendChild(postText);
         }
 
@@ -88,6 +120,16 @@ window.addEventListener("keydown", (event) => {
         return;
     }
 
+    if (event.key == "Backspace") {
+        invoke('undo_filter');
+        return;
+    }
+
+    if (event.altKey && event.key == "ArrowUp") {
+        invoke('navigate_up');
+        return;
+    }
+
     if (event.ctrlKey && event.key == ".") {
         invoke('open_config');
         return;
@@ -95,5 +137,9 @@ window.addEventListener("keydown", (event) => {
 
     if (event.key.length > 1) return;
 
-    invoke('filter_entries', {inChar: event.key});
+    invoke('filter_entries', {
+        inChar: event.key,
+        code: event.code,
+        modifiers: {ctrl: event.ctrlKey, alt: event.altKey, shift: event.shiftKey},
+    });
 });
\ No newline at end of file